@@ -0,0 +1,430 @@
+use crate::id_type::IdType;
+use crate::interval::Interval;
+use crate::interval_queue::IntervalQueue;
+use crate::intervals::Intervals;
+use crate::random::Random;
+use crate::reuse_policy::ReusePolicy;
+
+// allocate() and allocate_block() must only return values that are in free_ids, the
+// manager removes the values from free_ids once the strategy has chosen them, and fails
+// the allocation with InvalidStrategyChoice if they aren't free.
+// on_free() and on_used() report values that enter or leave free_ids by any other route.
+
+pub trait AllocationStrategy<T: IdType>: Send {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T>;
+
+    fn allocate_block(&mut self, free_ids: &Intervals<T>, count: T, alignment: T) -> Option<T> {
+        free_ids.find_aligned_block_at_or_after(T::MIN, count, alignment)
+    }
+
+    fn on_free(&mut self, _lower: T, _upper: T) {}
+
+    fn on_used(&mut self, _lower: T, _upper: T) {}
+
+    fn cursor(&self) -> Option<T> {
+        None
+    }
+
+    fn set_cursor(&mut self, _cursor: T) {}
+}
+
+pub trait IntoAllocationStrategy<T: IdType> {
+    fn into_allocation_strategy(self) -> Box<dyn AllocationStrategy<T>>;
+
+    // custom strategies have no policy, so a manager that uses one cannot be saved
+
+    fn reuse_policy(&self) -> Option<ReusePolicy> {
+        None
+    }
+}
+
+impl<T: IdType> IntoAllocationStrategy<T> for ReusePolicy {
+    fn into_allocation_strategy(self) -> Box<dyn AllocationStrategy<T>> {
+        match self {
+            ReusePolicy::ReuseFast => Box::new(FastStrategy),
+            ReusePolicy::ReuseSlow => Box::new(SlowStrategy::new()),
+            ReusePolicy::ReuseFastHighest => Box::new(FastHighestStrategy),
+            ReusePolicy::ReuseSlowDescending => Box::new(SlowDescendingStrategy::new()),
+            ReusePolicy::ReuseRandom { seed } => Box::new(RandomStrategy::new(seed)),
+            ReusePolicy::ReuseOldestFreed => Box::new(OldestFreedStrategy::new()),
+        }
+    }
+
+    fn reuse_policy(&self) -> Option<ReusePolicy> {
+        Some(*self)
+    }
+}
+
+impl<T: IdType, S: AllocationStrategy<T> + 'static> IntoAllocationStrategy<T> for Box<S> {
+    fn into_allocation_strategy(self) -> Box<dyn AllocationStrategy<T>> {
+        self
+    }
+}
+
+pub struct FastStrategy;
+
+impl<T: IdType> AllocationStrategy<T> for FastStrategy {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T> {
+        free_ids.first_value_at_or_after(T::MIN)
+    }
+}
+
+pub struct SlowStrategy<T: IdType> {
+    next_to_allocate: T,
+}
+
+impl<T: IdType> SlowStrategy<T> {
+    pub fn new() -> Self {
+        SlowStrategy { next_to_allocate: T::MIN }
+    }
+
+    fn move_past(&mut self, id: T) {
+        self.next_to_allocate = if id == T::MAX { T::MIN } else { id + T::one() };
+    }
+}
+
+impl<T: IdType> Default for SlowStrategy<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: IdType> AllocationStrategy<T> for SlowStrategy<T> {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T> {
+        let id = free_ids.first_value_at_or_after(self.next_to_allocate)
+            .or_else(|| free_ids.first_value_at_or_after(T::MIN))?;
+
+        self.move_past(id);
+
+        Some(id)
+    }
+
+    fn allocate_block(&mut self, free_ids: &Intervals<T>, count: T, alignment: T) -> Option<T> {
+        let lower = free_ids.find_aligned_block_at_or_after(self.next_to_allocate, count, alignment)
+            .or_else(|| free_ids.find_aligned_block_at_or_after(T::MIN, count, alignment))?;
+
+        self.move_past(lower + (count - T::one()));
+
+        Some(lower)
+    }
+
+    fn cursor(&self) -> Option<T> {
+        Some(self.next_to_allocate)
+    }
+
+    fn set_cursor(&mut self, cursor: T) {
+        self.next_to_allocate = cursor;
+    }
+}
+
+pub struct FastHighestStrategy;
+
+impl<T: IdType> AllocationStrategy<T> for FastHighestStrategy {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T> {
+        free_ids.last_value_at_or_before(T::MAX)
+    }
+
+    fn allocate_block(&mut self, free_ids: &Intervals<T>, count: T, alignment: T) -> Option<T> {
+        free_ids.find_aligned_block_at_or_before(T::MAX, count, alignment)
+    }
+}
+
+pub struct SlowDescendingStrategy<T: IdType> {
+    next_to_allocate: T,
+}
+
+impl<T: IdType> SlowDescendingStrategy<T> {
+    pub fn new() -> Self {
+        SlowDescendingStrategy { next_to_allocate: T::MAX }
+    }
+
+    fn move_past(&mut self, id: T) {
+        self.next_to_allocate = if id == T::MIN { T::MAX } else { id - T::one() };
+    }
+}
+
+impl<T: IdType> Default for SlowDescendingStrategy<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: IdType> AllocationStrategy<T> for SlowDescendingStrategy<T> {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T> {
+        let id = free_ids.last_value_at_or_before(self.next_to_allocate)
+            .or_else(|| free_ids.last_value_at_or_before(T::MAX))?;
+
+        self.move_past(id);
+
+        Some(id)
+    }
+
+    fn allocate_block(&mut self, free_ids: &Intervals<T>, count: T, alignment: T) -> Option<T> {
+        let lower = free_ids.find_aligned_block_at_or_before(self.next_to_allocate, count, alignment)
+            .or_else(|| free_ids.find_aligned_block_at_or_before(T::MAX, count, alignment))?;
+
+        self.move_past(lower);
+
+        Some(lower)
+    }
+
+    fn cursor(&self) -> Option<T> {
+        Some(self.next_to_allocate)
+    }
+
+    fn set_cursor(&mut self, cursor: T) {
+        self.next_to_allocate = cursor;
+    }
+}
+
+pub struct RandomStrategy {
+    random: Random,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: Option<u64>) -> Self {
+        RandomStrategy { random: Random::new(seed) }
+    }
+}
+
+impl<T: IdType> AllocationStrategy<T> for RandomStrategy {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T> {
+        free_ids.random_value(&mut self.random)
+    }
+}
+
+pub struct OldestFreedStrategy<T: IdType> {
+    freed_order: IntervalQueue<T, ()>,
+}
+
+impl<T: IdType> OldestFreedStrategy<T> {
+    pub fn new() -> Self {
+        OldestFreedStrategy { freed_order: IntervalQueue::new() }
+    }
+}
+
+impl<T: IdType> Default for OldestFreedStrategy<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: IdType> AllocationStrategy<T> for OldestFreedStrategy<T> {
+    fn allocate(&mut self, free_ids: &Intervals<T>) -> Option<T> {
+        let id = match self.freed_order.front() {
+            Some((oldest, _)) => oldest.lower(),
+            None => return free_ids.first_value_at_or_after(T::MIN),
+        };
+
+        self.freed_order.remove(id, id);
+
+        Some(id)
+    }
+
+    fn allocate_block(&mut self, free_ids: &Intervals<T>, count: T, alignment: T) -> Option<T> {
+        // the oldest freed run that can hold the whole block, otherwise the first block that fits
+        // anywhere, as there may be enough contiguous free ids that were freed at different times
+
+        let oldest = self.freed_order.iter().find_map(|(freed, _)| {
+            free_ids.find_aligned_block_at_or_after(freed.lower(), count, alignment)
+                .filter(|&lower| lower <= freed.upper() && freed.upper() - lower >= count - T::one())
+        });
+
+        let lower = match oldest {
+            Some(lower) => lower,
+            None => free_ids.find_aligned_block_at_or_after(T::MIN, count, alignment)?,
+        };
+
+        self.on_used(lower, lower + (count - T::one()));
+
+        Some(lower)
+    }
+
+    fn on_free(&mut self, lower: T, upper: T) {
+        if let Some((newest, _)) = self.freed_order.back() {
+            if newest.extends_upper(&Interval::new(lower, upper)) {
+                self.freed_order.remove(newest.lower(), newest.upper());
+
+                self.freed_order.push_back(newest.lower(), upper, ());
+
+                return;
+            }
+        }
+
+        self.freed_order.push_back(lower, upper, ());
+    }
+
+    fn on_used(&mut self, lower: T, upper: T) {
+        // ids leaving the free set other than via allocate() must leave the queue too,
+        // otherwise a stale entry could hand them out ahead of their turn once freed again
+
+        self.freed_order.remove(lower, upper);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intervals(lower: u8, upper: u8) -> Intervals<u8> {
+        let mut free_ids = Intervals::new();
+
+        free_ids.insert_interval(lower, upper);
+
+        free_ids
+    }
+
+    #[test]
+    fn test_fast_strategy() {
+        let mut free_ids = intervals(10, 20);
+
+        let mut strategy = FastStrategy;
+
+        assert_eq!(strategy.allocate(&free_ids), Some(10));
+        assert_eq!(strategy.allocate(&free_ids), Some(10));
+
+        free_ids.remove_value(10);
+
+        assert_eq!(strategy.allocate(&free_ids), Some(11));
+
+        assert_eq!(AllocationStrategy::<u8>::cursor(&strategy), None);
+
+        assert_eq!(strategy.allocate(&Intervals::<u8>::new()), None);
+    }
+
+    #[test]
+    fn test_slow_strategy() {
+        let mut free_ids = intervals(10, 20);
+
+        let mut strategy = SlowStrategy::new();
+
+        assert_eq!(strategy.allocate(&free_ids), Some(10));
+        assert_eq!(strategy.cursor(), Some(11));
+
+        assert_eq!(strategy.allocate(&free_ids), Some(11));
+
+        free_ids.remove_interval(12, 20);
+
+        assert_eq!(strategy.allocate(&free_ids), Some(10));
+
+        strategy.set_cursor(255);
+
+        assert_eq!(strategy.allocate(&intervals(250, 255)), Some(255));
+        assert_eq!(strategy.cursor(), Some(0));
+    }
+
+    #[test]
+    fn test_slow_strategy_block() {
+        let free_ids = intervals(10, 20);
+
+        let mut strategy = SlowStrategy::new();
+
+        assert_eq!(strategy.allocate_block(&free_ids, 5, 1), Some(10));
+        assert_eq!(strategy.cursor(), Some(15));
+
+        assert_eq!(strategy.allocate_block(&free_ids, 6, 1), Some(15));
+        assert_eq!(strategy.allocate_block(&free_ids, 5, 1), Some(10));
+    }
+
+    #[test]
+    fn test_fast_highest_strategy() {
+        let mut free_ids = intervals(10, 20);
+
+        let mut strategy = FastHighestStrategy;
+
+        assert_eq!(strategy.allocate(&free_ids), Some(20));
+
+        free_ids.remove_value(20);
+
+        assert_eq!(strategy.allocate(&free_ids), Some(19));
+
+        assert_eq!(strategy.allocate_block(&free_ids, 4, 4), Some(16));
+    }
+
+    #[test]
+    fn test_slow_descending_strategy() {
+        let mut free_ids = intervals(10, 20);
+
+        let mut strategy = SlowDescendingStrategy::new();
+
+        assert_eq!(strategy.allocate(&free_ids), Some(20));
+        assert_eq!(strategy.cursor(), Some(19));
+
+        assert_eq!(strategy.allocate(&free_ids), Some(19));
+
+        free_ids.remove_interval(10, 18);
+
+        assert_eq!(strategy.allocate(&free_ids), Some(20));
+
+        strategy.set_cursor(0);
+
+        assert_eq!(strategy.allocate(&intervals(0, 5)), Some(0));
+        assert_eq!(strategy.cursor(), Some(255));
+    }
+
+    #[test]
+    fn test_slow_descending_strategy_block() {
+        let free_ids = intervals(10, 20);
+
+        let mut strategy = SlowDescendingStrategy::new();
+
+        assert_eq!(strategy.allocate_block(&free_ids, 5, 1), Some(16));
+        assert_eq!(strategy.cursor(), Some(15));
+
+        assert_eq!(strategy.allocate_block(&free_ids, 6, 1), Some(10));
+        assert_eq!(strategy.allocate_block(&free_ids, 5, 1), Some(16));
+    }
+
+    #[test]
+    fn test_oldest_freed_strategy() {
+        let free_ids = intervals(0, 255);
+
+        let mut strategy = OldestFreedStrategy::new();
+
+        assert_eq!(strategy.allocate(&free_ids), Some(0));
+
+        strategy.on_free(10, 12);
+        strategy.on_free(13, 13);
+        strategy.on_free(5, 5);
+
+        assert_eq!(strategy.allocate(&free_ids), Some(10));
+
+        strategy.on_used(11, 12);
+
+        assert_eq!(strategy.allocate(&free_ids), Some(13));
+        assert_eq!(strategy.allocate(&free_ids), Some(5));
+
+        assert_eq!(strategy.allocate(&free_ids), Some(0));
+    }
+
+    #[test]
+    fn test_oldest_freed_strategy_allocate_block() {
+        let mut free_ids = intervals(0, 20);
+
+        let mut strategy = OldestFreedStrategy::new();
+
+        for i in 0..5 {
+            assert_eq!(strategy.allocate(&free_ids), Some(i));
+
+            free_ids.remove_value(i);
+        }
+
+        for i in [2, 3, 4, 0, 1] {
+            free_ids.insert_interval(i, i);
+
+            strategy.on_free(i, i);
+        }
+
+        assert_eq!(strategy.allocate_block(&free_ids, 3, 1), Some(2));
+
+        free_ids.remove_interval(2, 4);
+
+        // nothing freed can hold 4 ids, so the first block that fits
+
+        assert_eq!(strategy.allocate_block(&free_ids, 4, 1), Some(5));
+
+        free_ids.remove_interval(5, 8);
+
+        assert_eq!(strategy.allocate_block(&free_ids, 2, 2), Some(0));
+    }
+}
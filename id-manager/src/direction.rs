@@ -0,0 +1,6 @@
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Direction {
+    Nearest,
+    AtOrAbove,
+    AtOrBelow,
+}
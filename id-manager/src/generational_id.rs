@@ -0,0 +1,45 @@
+use std::fmt;
+
+use crate::id_type::IdType;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct GenerationalId<T: IdType> {
+    index: T,
+    generation: u32,
+}
+
+impl<T: IdType> GenerationalId<T> {
+    pub fn new(index: T, generation: u32) -> Self {
+        GenerationalId { index, generation }
+    }
+
+    pub fn index(&self) -> T {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl<T: IdType> fmt::Display for GenerationalId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}:{}]", self.index, self.generation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let id = GenerationalId::<u8>::new(10, 2);
+
+        assert_eq!(id.index(), 10);
+
+        assert_eq!(id.generation(), 2);
+
+        assert_eq!(format!("{}", id), "[10:2]");
+    }
+}
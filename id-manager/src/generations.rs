@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use crate::id_type::IdType;
+
+// the generation of every id, stored as runs of adjacent ids that share a generation so that
+// freeing a large interval doesn't add an entry per id. ids that aren't in a run are at
+// generation 0, and adjacent runs with the same generation are always merged.
+
+pub struct Generations<T: IdType> {
+    // (upper, generation) of each run, keyed by lower
+    runs: BTreeMap<T, (T, u32)>,
+}
+
+impl<T: IdType> Generations<T> {
+    pub fn new() -> Self {
+        Generations { runs: BTreeMap::new() }
+    }
+
+    pub fn generation_of(&self, id: T) -> u32 {
+        match self.runs.range(..=id).next_back() {
+            Some((_, &(upper, generation))) if upper >= id => generation,
+            _ => 0,
+        }
+    }
+
+    pub fn advance(&mut self, lower: T, upper: T) {
+        let mut overlapping = Vec::new();
+
+        if let Some((&run_lower, &(run_upper, generation))) = self.runs.range(..lower).next_back() {
+            if run_upper >= lower {
+                overlapping.push((run_lower, run_upper, generation));
+            }
+        }
+
+        overlapping.extend(self.runs.range(lower..=upper).map(|(&run_lower, &(run_upper, generation))| (run_lower, run_upper, generation)));
+
+        for (run_lower, _, _) in &overlapping {
+            self.runs.remove(run_lower);
+        }
+
+        // the parts of the first and last runs that are outside of lower and upper keep their generation
+
+        if let Some(&(run_lower, _, generation)) = overlapping.first() {
+            if run_lower < lower {
+                self.insert(run_lower, lower - T::one(), generation);
+            }
+        }
+
+        // the first id that isn't covered yet, None once upper has been reached
+
+        let mut next = Some(lower);
+
+        for &(run_lower, run_upper, generation) in &overlapping {
+            let from = if run_lower < lower { lower } else { run_lower };
+
+            let to = if run_upper > upper { upper } else { run_upper };
+
+            if let Some(next) = next {
+                if next < from {
+                    self.insert(next, from - T::one(), 1);
+                }
+            }
+
+            self.insert(from, to, generation.wrapping_add(1));
+
+            next = if to == upper { None } else { Some(to + T::one()) };
+        }
+
+        if let Some(next) = next {
+            self.insert(next, upper, 1);
+        }
+
+        if let Some(&(_, run_upper, generation)) = overlapping.last() {
+            if run_upper > upper {
+                self.insert(upper + T::one(), run_upper, generation);
+            }
+        }
+    }
+
+    // runs are inserted in order, so only the previous run needs to be checked for a merge until
+    // the last one, which may also join the run after it
+
+    fn insert(&mut self, mut lower: T, mut upper: T, generation: u32) {
+        if generation == 0 {
+            return;
+        }
+
+        if lower > T::MIN {
+            if let Some((&previous_lower, &(previous_upper, previous_generation))) = self.runs.range(..lower).next_back() {
+                if previous_upper == lower - T::one() && previous_generation == generation {
+                    self.runs.remove(&previous_lower);
+
+                    lower = previous_lower;
+                }
+            }
+        }
+
+        if upper < T::MAX {
+            if let Some(&(next_upper, next_generation)) = self.runs.get(&(upper + T::one())) {
+                if next_generation == generation {
+                    self.runs.remove(&(upper + T::one()));
+
+                    upper = next_upper;
+                }
+            }
+        }
+
+        self.runs.insert(lower, (upper, generation));
+    }
+
+    pub fn dump(&self) -> String {
+        let runs: Vec<String> = self.runs.iter()
+            .map(|(lower, (upper, generation))| format!("[{},{}]:{}", lower, upper, generation))
+            .collect();
+
+        runs.join(", ")
+    }
+}
+
+impl<T: IdType> Default for Generations<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generation_of() {
+        let mut generations = Generations::<u8>::new();
+
+        assert_eq!(generations.generation_of(0), 0);
+
+        generations.advance(10, 19);
+
+        assert_eq!(generations.generation_of(9), 0);
+        assert_eq!(generations.generation_of(10), 1);
+        assert_eq!(generations.generation_of(19), 1);
+        assert_eq!(generations.generation_of(20), 0);
+
+        assert_eq!(generations.dump(), "[10,19]:1");
+    }
+
+    #[test]
+    fn test_advance_splits_runs() {
+        let mut generations = Generations::<u8>::new();
+
+        generations.advance(10, 19);
+        generations.advance(14, 15);
+
+        assert_eq!(generations.dump(), "[10,13]:1, [14,15]:2, [16,19]:1");
+
+        generations.advance(5, 25);
+
+        assert_eq!(generations.dump(), "[5,9]:1, [10,13]:2, [14,15]:3, [16,19]:2, [20,25]:1");
+
+        assert_eq!(generations.generation_of(15), 3);
+        assert_eq!(generations.generation_of(16), 2);
+    }
+
+    #[test]
+    fn test_advance_merges_runs() {
+        let mut generations = Generations::<u8>::new();
+
+        generations.advance(10, 10);
+        generations.advance(12, 12);
+        generations.advance(11, 11);
+
+        assert_eq!(generations.dump(), "[10,12]:1");
+
+        generations.advance(10, 10);
+        generations.advance(12, 12);
+
+        assert_eq!(generations.dump(), "[10,10]:2, [11,11]:1, [12,12]:2");
+
+        generations.advance(11, 11);
+
+        assert_eq!(generations.dump(), "[10,12]:2");
+    }
+
+    #[test]
+    fn test_advance_full_range() {
+        let mut generations = Generations::<u8>::new();
+
+        generations.advance(0, 255);
+        generations.advance(255, 255);
+        generations.advance(0, 0);
+
+        assert_eq!(generations.dump(), "[0,0]:2, [1,254]:1, [255,255]:2");
+
+        generations.advance(0, 255);
+
+        assert_eq!(generations.dump(), "[0,0]:3, [1,254]:2, [255,255]:3");
+    }
+}
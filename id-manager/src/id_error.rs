@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::id_type::IdType;
+use crate::interval::Interval;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IdError {
+    Exhausted,
+    NotAllocated,
+    AlreadyAllocated,
+    Quarantined,
+    OutOfRange,
+    LockPoisoned,
+    InvalidCount,
+    InvalidAlignment,
+    InvalidStrategyChoice,
+    GenerationsDisabled,
+    StaleGeneration,
+    EmptyRange,
+    Reserved,
+    NoFreeIdInDirection,
+    NoSuitableBlock,
+    NotLeased,
+    LeaseExpired,
+    JournalFailed,
+}
+
+impl fmt::Display for IdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdError::Exhausted => write!(f, "No Ids available"),
+            IdError::NotAllocated => write!(f, "id is not currently allocated"),
+            IdError::AlreadyAllocated => write!(f, "id is already allocated"),
+            IdError::Quarantined => write!(f, "id is quarantined"),
+            IdError::OutOfRange => write!(f, "id out of range"),
+            IdError::LockPoisoned => write!(f, "id manager lock poisoned"),
+            IdError::InvalidCount => write!(f, "count must be at least one"),
+            IdError::InvalidAlignment => write!(f, "alignment must be at least one"),
+            IdError::InvalidStrategyChoice => write!(f, "allocation strategy chose ids that are not free"),
+            IdError::GenerationsDisabled => write!(f, "generations are not enabled"),
+            IdError::StaleGeneration => write!(f, "id generation is stale"),
+            IdError::EmptyRange => write!(f, "new range must contain at least one id"),
+            IdError::Reserved => write!(f, "id is reserved"),
+            IdError::NoFreeIdInDirection => write!(f, "no free id in that direction"),
+            IdError::NoSuitableBlock => write!(f, "no free block is large enough"),
+            IdError::NotLeased => write!(f, "id is not leased"),
+            IdError::LeaseExpired => write!(f, "id lease has expired"),
+            IdError::JournalFailed => write!(f, "journal write failed, compact the journal to continue"),
+        }
+    }
+}
+
+impl Error for IdError {}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ShrinkRangeError<T: IdType> {
+    Failed(IdError),
+    // the allocated ids that lie outside of the new range
+    Allocated(Vec<Interval<T>>),
+}
+
+impl<T: IdType> fmt::Display for ShrinkRangeError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShrinkRangeError::Failed(error) => write!(f, "{}", error),
+            ShrinkRangeError::Allocated(_) => write!(f, "ids are allocated outside of the new range"),
+        }
+    }
+}
+
+impl<T: IdType + fmt::Debug> Error for ShrinkRangeError<T> {}
+
+impl<T: IdType> From<IdError> for ShrinkRangeError<T> {
+    fn from(error: IdError) -> Self {
+        ShrinkRangeError::Failed(error)
+    }
+}
@@ -1,312 +1,2467 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::allocation_strategy::{AllocationStrategy, IntoAllocationStrategy};
+use crate::clock::{Clock, SystemClock};
+use crate::direction::Direction;
+use crate::generational_id::GenerationalId;
+use crate::generations::Generations;
+use crate::id_error::{IdError, ShrinkRangeError};
 use crate::id_type::IdType;
+use crate::interval::Interval;
 use crate::intervals::Intervals;
+use crate::journal::{Journal, JournalError, JournalRecord};
+use crate::lease::Lease;
+use crate::leak_detection::{LeakDetection, TrackedAllocation};
+use crate::observer::IdManagerObserver;
+use crate::quarantine::{Quarantine, QuarantinePeriod};
+use crate::reservation::Reservation;
 use crate::reuse_policy::ReusePolicy;
+use crate::snapshot::{self, SnapshotError};
+use crate::state::{IdManagerState, StateError};
+use crate::values::Values;
 
 pub struct IdManager<T: IdType> {
     free_ids: Intervals<T>,
-    reuse_policy: ReusePolicy,
-    next_to_allocate: T,
-    min_id: T,
-    max_id: T,
+    strategy: Box<dyn AllocationStrategy<T>>,
+    // None when a custom strategy is used
+    policy: Option<ReusePolicy>,
+    quarantine: Option<Quarantine<T>>,
+    allocations: u64,
+    clock: Box<dyn Clock>,
+    // generations of ids that have been freed at least once, ids that are missing are at generation 0
+    generations: Option<Generations<T>>,
+    // the ids that this manager is responsible for, free or not
+    ranges: Intervals<T>,
+    // keyed by the lowest id of each reservation, reservations never overlap
+    reservations: BTreeMap<T, Reservation<T>>,
+    reserved_ids: Intervals<T>,
+    observers: Vec<Arc<dyn IdManagerObserver<T>>>,
+    low_watermark: Option<u128>,
+    below_low_watermark: bool,
+    leak_detection: Option<LeakDetection<T>>,
+    // the owner of each id that was allocated for an owner, entries are removed when the id is freed
+    owners: BTreeMap<T, String>,
+    // the expiry of each id that was leased, entries are removed when the id is freed
+    leases: BTreeMap<T, Instant>,
+    journal: Option<Journal>,
 }
 
 impl<T: IdType> IdManager<T> {
-    pub fn new(reuse_policy: ReusePolicy) -> Self {
-        Self::new_limited_range(reuse_policy, T::MIN, T::MAX)
+    pub fn new(strategy: impl IntoAllocationStrategy<T>) -> Self {
+        Self::new_limited_range(strategy, T::MIN, T::MAX)
     }
 
-    pub fn new_limited_range(reuse_policy: ReusePolicy, min_id: T, max_id: T) -> Self {
-        let mut manager = IdManager::<T> { free_ids: Intervals::<T>::new(), reuse_policy, next_to_allocate: min_id, min_id, max_id };
+    pub fn new_limited_range(strategy: impl IntoAllocationStrategy<T>, min_id: T, max_id: T) -> Self {
+        Self::new_with_ranges(strategy, &[min_id..=max_id])
+    }
+
+    pub fn new_with_ranges(strategy: impl IntoAllocationStrategy<T>, ranges: &[RangeInclusive<T>]) -> Self {
+        if ranges.is_empty() {
+            panic!("at least one range is required");
+        }
+
+        let mut manager = IdManager::<T> { free_ids: Intervals::<T>::new(), policy: strategy.reuse_policy(), strategy: strategy.into_allocation_strategy(), quarantine: None, allocations: 0, clock: Box::new(SystemClock), generations: None, ranges: Intervals::<T>::new(), reservations: BTreeMap::new(), reserved_ids: Intervals::<T>::new(), observers: Vec::new(), low_watermark: None, below_low_watermark: false, leak_detection: None, owners: BTreeMap::new(), leases: BTreeMap::new(), journal: None };
 
-        manager.free_ids.insert_interval(min_id, max_id);
+        for range in ranges {
+            manager.add_range(*range.start(), *range.end());
+        }
 
         manager
     }
 
+    fn add_range(&mut self, lower: T, upper: T) {
+        for gap in self.ranges.gaps(lower, upper) {
+            self.ranges.insert_interval(gap.lower(), gap.upper());
+
+            self.add_free_ids(gap.lower(), gap.upper());
+        }
+    }
+
+    pub fn to_state(&self) -> Result<IdManagerState<T>, StateError> {
+        let policy = self.policy.ok_or(StateError::CustomStrategy)?;
+
+        let mut free_ids = Intervals::<T>::new();
+
+        for interval in self.free_ids.iter() {
+            free_ids.insert_interval(interval.lower(), interval.upper());
+        }
+
+        if let Some(quarantine) = &self.quarantine {
+            for interval in quarantine.iter() {
+                free_ids.insert_interval(interval.lower(), interval.upper());
+            }
+        }
+
+        Ok(IdManagerState {
+            min_id: self.min_id(),
+            max_id: self.max_id(),
+            policy,
+            next_to_allocate: self.strategy.cursor(),
+            ranges: self.ranges.iter().cloned().collect(),
+            free_ids: free_ids.iter().cloned().collect(),
+            reservations: self.reservations(),
+        })
+    }
+
+    pub fn from_state(state: IdManagerState<T>) -> Result<Self, StateError> {
+        if state.ranges.is_empty() {
+            return Err(StateError::NoRanges);
+        }
+
+        let ranges = Intervals::try_from_sorted(&state.ranges)?;
+
+        let free_ids = Intervals::try_from_sorted(&state.free_ids)?;
+
+        if state.min_id != state.ranges[0].lower() || state.max_id != state.ranges[state.ranges.len() - 1].upper() {
+            return Err(StateError::RangeMismatch);
+        }
+
+        if state.free_ids.iter().any(|interval| !ranges.contains_all(interval.lower(), interval.upper())) {
+            return Err(StateError::OutOfRange);
+        }
+
+        let mut reserved_ids = Intervals::<T>::new();
+
+        for reservation in &state.reservations {
+            if reservation.upper() < reservation.lower() {
+                return Err(StateError::InvalidInterval);
+            }
+
+            if !ranges.contains_all(reservation.lower(), reservation.upper()) {
+                return Err(StateError::OutOfRange);
+            }
+
+            if free_ids.contains_any(reservation.lower(), reservation.upper()) || !reserved_ids.insert_interval(reservation.lower(), reservation.upper()) {
+                return Err(StateError::ReservationNotAllocated);
+            }
+        }
+
+        let range_list: Vec<RangeInclusive<T>> = state.ranges.iter().map(|range| range.lower()..=range.upper()).collect();
+
+        let mut manager = Self::new_with_ranges(state.policy, &range_list);
+
+        for range in &state.ranges {
+            for used in free_ids.gaps(range.lower(), range.upper()) {
+                manager.remove_free_ids(used.lower(), used.upper());
+            }
+        }
+
+        if let Some(next_to_allocate) = state.next_to_allocate {
+            manager.strategy.set_cursor(next_to_allocate);
+        }
+
+        for reservation in state.reservations {
+            manager.reservations.insert(reservation.lower(), reservation);
+        }
+
+        manager.reserved_ids = reserved_ids;
+
+        Ok(manager)
+    }
+
+    pub fn write_snapshot(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        snapshot::write_snapshot(&self.to_state()?, writer)
+    }
+
+    pub fn read_snapshot(reader: impl Read) -> Result<Self, SnapshotError> {
+        Ok(Self::from_state(snapshot::read_snapshot(reader)?)?)
+    }
+
+    pub fn enable_journal(&mut self, path: impl AsRef<Path>) -> Result<(), JournalError> {
+        let state = self.to_state()?;
+
+        self.journal = Some(Journal::create(path, &state)?);
+
+        Ok(())
+    }
+
+    pub fn compact_journal(&mut self) -> Result<(), JournalError> {
+        let state = self.to_state()?;
+
+        match &mut self.journal {
+            Some(journal) => journal.compact(&state),
+            None => Err(JournalError::NotEnabled),
+        }
+    }
+
+    pub fn recover(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        let (journal, state, records) = Journal::recover::<T>(path)?;
+
+        let mut manager = Self::from_state(state)?;
+
+        for record in records {
+            manager.replay(record).map_err(JournalError::Replay)?;
+        }
+
+        manager.journal = Some(journal);
+
+        Ok(manager)
+    }
+
+    // records hold the ids that were chosen, rather than the request, so that replay doesn't depend
+    // on the strategy making the same choices again
+
+    fn replay(&mut self, record: JournalRecord<T>) -> Result<(), IdError> {
+        match record {
+            JournalRecord::Allocated { lower, upper, cursor } => {
+                self.replay_used(lower, upper)?;
+
+                if let Some(cursor) = cursor {
+                    self.strategy.set_cursor(cursor);
+                }
+
+                Ok(())
+            }
+            JournalRecord::MarkedUsed { lower, upper } => self.replay_used(lower, upper),
+            JournalRecord::Freed { lower, upper } => self.try_free_interval(lower, upper),
+        }
+    }
+
+    fn replay_used(&mut self, lower: T, upper: T) -> Result<(), IdError> {
+        if !self.ranges.contains_all(lower, upper) {
+            return Err(IdError::OutOfRange);
+        }
+
+        self.remove_free_ids(lower, upper);
+
+        Ok(())
+    }
+
+    fn journal(&mut self, record: JournalRecord<T>) {
+        if let Some(journal) = &mut self.journal {
+            journal.append(record);
+        }
+    }
+
+    // ranges and reservations have no journal records, so changes to them are saved by compacting
+
+    fn journal_snapshot(&mut self) {
+        if self.journal.is_some() && self.compact_journal().is_err() {
+            if let Some(journal) = &mut self.journal {
+                journal.mark_failed();
+            }
+        }
+    }
+
+    fn check_journal(&self) -> Result<(), IdError> {
+        match &self.journal {
+            Some(journal) if journal.has_failed() => Err(IdError::JournalFailed),
+            _ => Ok(()),
+        }
+    }
+
+    fn min_id(&self) -> T {
+        self.ranges.first_value_at_or_after(T::MIN).unwrap()
+    }
+
+    fn max_id(&self) -> T {
+        self.ranges.last_value_at_or_before(T::MAX).unwrap()
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn set_quarantine(&mut self, period: Option<QuarantinePeriod>) {
+        self.flush_quarantine();
+
+        self.quarantine = period.map(Quarantine::new);
+    }
+
+    pub fn enable_generations(&mut self) {
+        if self.generations.is_none() {
+            self.generations = Some(Generations::new());
+        }
+    }
+
+    pub fn flush_quarantine(&mut self) {
+        if let Some(quarantine) = &mut self.quarantine {
+            for interval in quarantine.flush() {
+                self.add_free_ids(interval.lower(), interval.upper());
+            }
+        }
+    }
+
+    fn release_expired_quarantine(&mut self) {
+        if let Some(quarantine) = &mut self.quarantine {
+            for interval in quarantine.release_expired(self.allocations, self.clock.now()) {
+                self.add_free_ids(interval.lower(), interval.upper());
+            }
+        }
+    }
+
+    fn add_free_ids(&mut self, lower: T, upper: T) {
+        self.free_ids.insert_interval(lower, upper);
+
+        self.strategy.on_free(lower, upper);
+
+        self.check_low_watermark();
+    }
+
+    fn remove_free_ids(&mut self, lower: T, upper: T) {
+        self.free_ids.remove_interval(lower, upper);
+
+        self.strategy.on_used(lower, upper);
+
+        if let Some(quarantine) = &mut self.quarantine {
+            quarantine.remove(lower, upper);
+        }
+
+        self.check_low_watermark();
+    }
+
+    pub fn extend_range(&mut self, new_min: T, new_max: T) {
+        if let Err(error) = self.try_extend_range(new_min, new_max) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_extend_range(&mut self, new_min: T, new_max: T) -> Result<(), IdError> {
+        if new_min > self.min_id() || new_max < self.max_id() {
+            return Err(IdError::OutOfRange);
+        }
+
+        // only the ids outside of the current range are added, gaps between disjoint ranges
+        // belong to something else and stay out of the range
+
+        if new_min < self.min_id() {
+            let min_id = self.min_id();
+
+            self.add_range(new_min, min_id - T::one());
+        }
+
+        if new_max > self.max_id() {
+            let max_id = self.max_id();
+
+            self.add_range(max_id + T::one(), new_max);
+        }
+
+        self.journal_snapshot();
+
+        Ok(())
+    }
+
+    // fails with the allocated ids that lie outside of the new range, if any
+
+    pub fn shrink_range(&mut self, new_min: T, new_max: T) -> Result<(), Vec<Interval<T>>> {
+        match self.try_shrink_range(new_min, new_max) {
+            Ok(()) => Ok(()),
+            Err(ShrinkRangeError::Allocated(allocated)) => Err(allocated),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_shrink_range(&mut self, new_min: T, new_max: T) -> Result<(), ShrinkRangeError<T>> {
+        if new_max < new_min || new_min < self.min_id() || new_max > self.max_id() {
+            return Err(IdError::OutOfRange.into());
+        }
+
+        if !self.ranges.contains_any(new_min, new_max) {
+            return Err(IdError::EmptyRange.into());
+        }
+
+        let mut removed = Vec::new();
+
+        if new_min > self.min_id() {
+            removed.extend(self.ranges.intersection(self.min_id(), new_min - T::one()));
+        }
+
+        if new_max < self.max_id() {
+            removed.extend(self.ranges.intersection(new_max + T::one(), self.max_id()));
+        }
+
+        let allocated: Vec<Interval<T>> = removed.iter()
+            .flat_map(|interval| self.used_within(interval.lower(), interval.upper()))
+            .collect();
+
+        if !allocated.is_empty() {
+            return Err(ShrinkRangeError::Allocated(allocated));
+        }
+
+        for interval in removed {
+            self.remove_free_ids(interval.lower(), interval.upper());
+
+            self.ranges.remove_interval(interval.lower(), interval.upper());
+        }
+
+        self.journal_snapshot();
+
+        Ok(())
+    }
+
+    // ids that are neither free nor quarantined, this includes reserved ids
+
+    fn used_within(&self, lower: T, upper: T) -> Vec<Interval<T>> {
+        let not_free = self.free_ids.gaps(lower, upper);
+
+        match &self.quarantine {
+            Some(quarantine) => not_free.iter()
+                .flat_map(|interval| quarantine.gaps(interval.lower(), interval.upper()))
+                .collect(),
+            None => not_free,
+        }
+    }
+
+    pub fn reserve(&mut self, lower: T, upper: T, label: &str) -> Result<(), IdError> {
+        if upper < lower || !self.ranges.contains_all(lower, upper) {
+            return Err(IdError::OutOfRange);
+        }
+
+        if self.is_reserved(lower, upper) {
+            return Err(IdError::Reserved);
+        }
+
+        self.release_expired_quarantine();
+
+        if self.is_quarantined(lower, upper) {
+            return Err(IdError::Quarantined);
+        }
+
+        if !self.free_ids.contains_all(lower, upper) {
+            return Err(IdError::AlreadyAllocated);
+        }
+
+        self.remove_free_ids(lower, upper);
+
+        self.reservations.insert(lower, Reservation::new(lower, upper, label));
+
+        self.reserved_ids.insert_interval(lower, upper);
+
+        self.journal_snapshot();
+
+        Ok(())
+    }
+
+    pub fn reservations(&self) -> Vec<Reservation<T>> {
+        self.reservations.values().cloned().collect()
+    }
+
+    fn is_reserved(&self, lower: T, upper: T) -> bool {
+        self.reserved_ids.contains_any(lower, upper)
+    }
+
     pub fn dump(&self) -> String {
-        self.free_ids.dump()
+        match &self.quarantine {
+            Some(quarantine) if !quarantine.is_empty() => {
+                if self.free_ids.is_empty() {
+                    format!("quarantined: {}", quarantine.dump())
+                } else {
+                    format!("{} quarantined: {}", self.free_ids.dump(), quarantine.dump())
+                }
+            }
+            _ => self.free_ids.dump(),
+        }
+    }
+
+    pub fn is_free(&self, id: T) -> bool {
+        self.free_ids.contains_value(id)
+    }
+
+    // quarantined and reserved ids are neither free nor allocated
+
+    pub fn is_allocated(&self, id: T) -> bool {
+        self.ranges.contains_value(id) && !self.free_ids.contains_value(id) && !self.is_quarantined(id, id) && !self.is_reserved(id, id)
+    }
+
+    pub fn next_free_at_or_after(&self, id: T) -> Option<T> {
+        self.free_ids.first_value_at_or_after(id)
+    }
+
+    pub fn prev_free_at_or_before(&self, id: T) -> Option<T> {
+        self.free_ids.last_value_at_or_before(id)
+    }
+
+    // counts are None when they are 2^128, which can only happen when managing the full u128 range
+
+    pub fn capacity(&self) -> Option<u128> {
+        self.ranges.count()
+    }
+
+    pub fn free_count(&self) -> Option<u128> {
+        self.free_ids.count()
+    }
+
+    pub fn allocated_count(&self) -> Option<u128> {
+        let quarantined = match &self.quarantine {
+            Some(quarantine) => quarantine.count(),
+            None => Some(0),
+        };
+
+        let not_allocated = self.free_count().unwrap_or(0)
+            .wrapping_add(quarantined.unwrap_or(0))
+            .wrapping_add(self.reserved_ids.count().unwrap_or(0));
+
+        if self.capacity().is_none() && self.free_ids.is_empty() && quarantined == Some(0) && self.reservations.is_empty() {
+            return None;
+        }
+
+        Some(self.capacity().unwrap_or(0).wrapping_sub(not_allocated))
+    }
+
+    pub fn interval_count(&self) -> usize {
+        self.free_ids.interval_count()
+    }
+
+    pub fn largest_free_block(&self) -> Option<Interval<T>> {
+        self.free_ids.largest_interval()
+    }
+
+    pub fn free_intervals(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.free_ids.iter().map(|interval| interval.lower()..=interval.upper())
+    }
+
+    pub fn allocated_intervals(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.ranges.iter()
+            .flat_map(move |range| self.used_within(range.lower(), range.upper()))
+            .flat_map(move |used| self.reserved_ids.gaps(used.lower(), used.upper()))
+            .map(|interval| interval.lower()..=interval.upper())
+    }
+
+    pub fn free_values(&self) -> impl Iterator<Item = T> + '_ {
+        Values::new(self.free_intervals())
+    }
+
+    pub fn allocated_values(&self) -> impl Iterator<Item = T> + '_ {
+        Values::new(self.allocated_intervals())
     }
 
     pub fn can_allocate(&self) -> bool {
-        !self.free_ids.is_empty()
+        if !self.free_ids.is_empty() {
+            return true;
+        }
+
+        match &self.quarantine {
+            Some(quarantine) => quarantine.has_expired(self.allocations, self.clock.now()),
+            None => false,
+        }
     }
 
     pub fn allocate(&mut self) -> T {
-        if self.free_ids.is_empty()
-        {
-            panic!("No Ids available")
+        match self.try_allocate() {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
         }
+    }
 
-        if self.reuse_policy == ReusePolicy::ReuseFast
-        {
-            return self.free_ids.remove_first_value();
+    pub fn try_allocate(&mut self) -> Result<T, IdError> {
+        self.check_journal()?;
+
+        self.release_expired_quarantine();
+
+        let id = self.allocate_using_strategy()?;
+
+        self.allocated(id, id)?;
+
+        Ok(id)
+    }
+
+    pub fn allocate_for(&mut self, owner: &str) -> T {
+        match self.try_allocate_for(owner) {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
         }
+    }
+
+    pub fn try_allocate_for(&mut self, owner: &str) -> Result<T, IdError> {
+        let id = self.try_allocate()?;
 
-        let id: T;
+        self.owners.insert(id, owner.to_string());
 
-        loop {
-            if self.free_ids.remove_value(self.next_to_allocate)
-            {
-                id = self.next_to_allocate;
+        Ok(id)
+    }
 
-                self.next_to_allocate = self.increment_id(self.next_to_allocate);
+    pub fn owner_of(&self, id: T) -> Option<&str> {
+        self.owners.get(&id).map(String::as_str)
+    }
 
-                break;
-            }
+    pub fn ids_owned_by(&self, owner: &str) -> Vec<T> {
+        self.owners.iter().filter(|(_, id_owner)| *id_owner == owner).map(|(id, _)| *id).collect()
+    }
 
-            self.next_to_allocate = self.increment_id(self.next_to_allocate);
+    pub fn free_all_owned_by(&mut self, owner: &str) -> Vec<T> {
+        let ids = self.ids_owned_by(owner);
+
+        for id in &ids {
+            self.free(*id);
         }
 
-        id
+        ids
     }
 
-    pub fn mark_value_as_used(&mut self, id: T) {
-        if id < self.min_id && id > self.max_id {
-            panic!("id out of range");
+    pub fn lease(&mut self, ttl: Duration) -> Lease<T> {
+        match self.try_lease(ttl) {
+            Ok(lease) => lease,
+            Err(error) => panic!("{}", error),
         }
+    }
 
-        self.free_ids.remove_value(id);
+    pub fn try_lease(&mut self, ttl: Duration) -> Result<Lease<T>, IdError> {
+        let id = self.try_allocate()?;
+
+        let expiry = self.clock.now() + ttl;
+
+        self.leases.insert(id, expiry);
+
+        Ok(Lease::new(id, expiry))
     }
 
-    pub fn mark_interval_as_used(&mut self, lower: T, upper: T) {
-        if lower < self.min_id && lower > self.max_id {
-            panic!("lower id out of range");
+    pub fn renew(&mut self, id: T, ttl: Duration) -> Result<Lease<T>, IdError> {
+        let now = self.clock.now();
+
+        let expiry = self.leases.get_mut(&id).ok_or(IdError::NotLeased)?;
+
+        // an expired lease may already have been handed to someone else by the time it is renewed
+
+        if *expiry <= now {
+            return Err(IdError::LeaseExpired);
+        }
+
+        *expiry = now + ttl;
+
+        Ok(Lease::new(id, *expiry))
+    }
+
+    pub fn lease_of(&self, id: T) -> Option<Lease<T>> {
+        self.leases.get(&id).map(|expiry| Lease::new(id, *expiry))
+    }
+
+    pub fn reap_expired(&mut self, now: Instant) -> Vec<T> {
+        let expired: Vec<T> = self.leases.iter().filter(|(_, expiry)| **expiry <= now).map(|(id, _)| *id).collect();
+
+        for id in &expired {
+            self.free(*id);
         }
 
-        if upper < self.min_id && upper > self.max_id {
-            panic!("upper id out of range");
-        }
+        expired
+    }
+
+    fn allocate_using_strategy(&mut self) -> Result<T, IdError> {
+        let id = self.strategy.allocate(&self.free_ids).ok_or_else(|| self.exhausted())?;
+
+        // a custom strategy may get it wrong, which mustn't hand out an id that's in use
+
+        if !self.free_ids.remove_value(id)
+        {
+            return Err(IdError::InvalidStrategyChoice);
+        }
+
+        Ok(id)
+    }
+
+    pub fn allocate_generational(&mut self) -> GenerationalId<T> {
+        match self.try_allocate_generational() {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_allocate_generational(&mut self) -> Result<GenerationalId<T>, IdError> {
+        if self.generations.is_none() {
+            return Err(IdError::GenerationsDisabled);
+        }
+
+        let id = self.try_allocate()?;
+
+        Ok(GenerationalId::new(id, self.generation_of(id)))
+    }
+
+    pub fn is_current(&self, id: GenerationalId<T>) -> bool {
+        self.generations.is_some() && self.is_allocated(id.index()) && self.generation_of(id.index()) == id.generation()
+    }
+
+    pub fn allocate_specific(&mut self, id: T) -> T {
+        match self.try_allocate_specific(id) {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_allocate_specific(&mut self, id: T) -> Result<T, IdError> {
+        self.check_journal()?;
+
+        if !self.ranges.contains_value(id) {
+            return Err(IdError::OutOfRange);
+        }
+
+        if self.is_reserved(id, id) {
+            return Err(IdError::Reserved);
+        }
+
+        self.release_expired_quarantine();
+
+        if self.is_quarantined(id, id) {
+            return Err(IdError::Quarantined);
+        }
+
+        if !self.free_ids.remove_value(id)
+        {
+            return Err(IdError::AlreadyAllocated);
+        }
+
+        self.strategy.on_used(id, id);
+
+        self.allocated(id, id)?;
+
+        Ok(id)
+    }
+
+    pub fn allocate_near(&mut self, hint: T, direction: Direction) -> T {
+        match self.try_allocate_near(hint, direction) {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_allocate_near(&mut self, hint: T, direction: Direction) -> Result<T, IdError> {
+        self.check_journal()?;
+
+        self.release_expired_quarantine();
+
+        let above = || self.free_ids.first_value_at_or_after(hint);
+
+        let below = || self.free_ids.last_value_at_or_before(hint);
+
+        let id = match direction {
+            Direction::AtOrAbove => above(),
+            Direction::AtOrBelow => below(),
+            Direction::Nearest => match (above(), below()) {
+                (Some(above), Some(below)) => if above - hint <= hint - below { Some(above) } else { Some(below) },
+                (above, below) => above.or(below),
+            },
+        };
+
+        let id = id.ok_or_else(|| self.exhausted_or(IdError::NoFreeIdInDirection))?;
+
+        self.free_ids.remove_value(id);
+
+        self.strategy.on_used(id, id);
+
+        self.allocated(id, id)?;
+
+        Ok(id)
+    }
+
+    pub fn allocate_block(&mut self, count: T) -> Interval<T> {
+        match self.try_allocate_block(count) {
+            Ok(block) => block,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_allocate_block(&mut self, count: T) -> Result<Interval<T>, IdError> {
+        self.try_allocate_aligned(count, T::one())
+    }
+
+    pub fn allocate_aligned(&mut self, count: T, alignment: T) -> Interval<T> {
+        match self.try_allocate_aligned(count, alignment) {
+            Ok(block) => block,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_allocate_aligned(&mut self, count: T, alignment: T) -> Result<Interval<T>, IdError> {
+        if count < T::one() {
+            return Err(IdError::InvalidCount);
+        }
+
+        if alignment < T::one() {
+            return Err(IdError::InvalidAlignment);
+        }
+
+        self.check_journal()?;
+
+        self.release_expired_quarantine();
+
+        let lower = self.strategy.allocate_block(&self.free_ids, count, alignment).ok_or_else(|| self.exhausted_or(IdError::NoSuitableBlock))?;
+
+        // checked before working out upper, as a block starting at a bad lower may not fit in T
+
+        if !self.ranges.contains_value(lower) || count - T::one() > self.max_id() - lower {
+            return Err(IdError::InvalidStrategyChoice);
+        }
+
+        let upper = lower + (count - T::one());
+
+        if !self.free_ids.contains_all(lower, upper)
+        {
+            return Err(IdError::InvalidStrategyChoice);
+        }
+
+        self.free_ids.remove_interval(lower, upper);
+
+        self.allocated(lower, upper)?;
+
+        Ok(Interval::new(lower, upper))
+    }
+
+    pub fn mark_value_as_used(&mut self, id: T) {
+        if let Err(error) = self.try_mark_value_as_used(id) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_mark_value_as_used(&mut self, id: T) -> Result<(), IdError> {
+        self.try_mark_interval_as_used(id, id)
+    }
+
+    pub fn mark_interval_as_used(&mut self, lower: T, upper: T) {
+        if let Err(error) = self.try_mark_interval_as_used(lower, upper) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_mark_interval_as_used(&mut self, lower: T, upper: T) -> Result<(), IdError> {
+        if lower < self.min_id() || lower > self.max_id() {
+            return Err(IdError::OutOfRange);
+        }
+
+        if upper < self.min_id() || upper > self.max_id() {
+            return Err(IdError::OutOfRange);
+        }
+
+        self.check_journal()?;
+
+        self.remove_free_ids(lower, upper);
+
+        // an interval can span the gaps between disjoint ranges, only the ids within the ranges
+        // are journalled as replay rejects anything else
+
+        for interval in self.ranges.intersection(lower, upper) {
+            self.journal(JournalRecord::MarkedUsed { lower: interval.lower(), upper: interval.upper() });
+        }
+
+        self.notify(|observer| observer.on_mark_used(lower, upper));
+
+        self.check_journal()
+    }
+
+    pub fn free(&mut self, id: T) {
+        if let Err(error) = self.try_free(id) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn free_interval(&mut self, lower: T, upper: T) {
+        if let Err(error) = self.try_free_interval(lower, upper) {
+            panic!("{}", error);
+        }
+    }
+
+    // nothing is freed unless the whole interval can be
+
+    pub fn try_free_interval(&mut self, lower: T, upper: T) -> Result<(), IdError> {
+        if upper < lower || !self.ranges.contains_all(lower, upper) {
+            return Err(IdError::OutOfRange);
+        }
+
+        if self.is_reserved(lower, upper) {
+            return Err(IdError::Reserved);
+        }
+
+        if self.is_quarantined(lower, upper) {
+            return Err(IdError::NotAllocated);
+        }
+
+        if let Some(quarantine) = &mut self.quarantine {
+            if self.free_ids.contains_any(lower, upper) {
+                return Err(IdError::NotAllocated);
+            }
+
+            quarantine.insert(lower, upper, self.allocations, self.clock.now());
+
+            self.freed(lower, upper);
+
+            return Ok(());
+        }
+
+        if !self.free_ids.insert_interval(lower, upper)
+        {
+            return Err(IdError::NotAllocated);
+        }
+
+        self.strategy.on_free(lower, upper);
+
+        self.freed(lower, upper);
+
+        Ok(())
+    }
+
+    pub fn try_free(&mut self, id: T) -> Result<(), IdError> {
+        self.try_free_interval(id, id)
+    }
+
+    pub fn free_generational(&mut self, id: GenerationalId<T>) {
+        if let Err(error) = self.try_free_generational(id) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn try_free_generational(&mut self, id: GenerationalId<T>) -> Result<(), IdError> {
+        if self.generations.is_none() {
+            return Err(IdError::GenerationsDisabled);
+        }
+
+        if self.generation_of(id.index()) != id.generation() {
+            return Err(IdError::StaleGeneration);
+        }
+
+        self.try_free(id.index())
+    }
+
+    pub fn enable_leak_detection(&mut self, capture_backtraces: bool) {
+        if self.leak_detection.is_none() {
+            self.leak_detection = Some(LeakDetection::new(capture_backtraces));
+        }
+    }
+
+    pub fn track_allocation(&mut self, lower: T, upper: T, location: &'static Location<'static>) {
+        if let Some(leak_detection) = &mut self.leak_detection {
+            leak_detection.allocated(lower, upper, location);
+        }
+    }
+
+    pub fn track_release(&mut self, lower: T) {
+        if let Some(leak_detection) = &mut self.leak_detection {
+            leak_detection.released(lower);
+        }
+    }
+
+    pub fn outstanding_allocations(&self) -> Vec<TrackedAllocation<T>> {
+        match &self.leak_detection {
+            Some(leak_detection) => leak_detection.outstanding(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn released_allocations(&self) -> Vec<TrackedAllocation<T>> {
+        match &self.leak_detection {
+            Some(leak_detection) => leak_detection.released_allocations(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn leak_report(&self) -> String {
+        let mut report = String::new();
+
+        for allocation in self.outstanding_allocations() {
+            report.push_str(&format!("outstanding: {}\n", allocation));
+        }
+
+        for allocation in self.released_allocations() {
+            report.push_str(&format!("released: {}\n", allocation));
+        }
+
+        report
+    }
+
+    pub fn add_observer(&mut self, observer: Arc<dyn IdManagerObserver<T>>) {
+        self.observers.push(observer);
+    }
+
+    pub fn set_low_watermark(&mut self, watermark: Option<u128>) {
+        self.low_watermark = watermark;
+
+        self.below_low_watermark = false;
+
+        self.check_low_watermark();
+    }
+
+    fn notify<F: Fn(&dyn IdManagerObserver<T>)>(&self, callback: F) {
+        for observer in &self.observers {
+            callback(observer.as_ref());
+        }
+    }
+
+    // an allocation that can't be journalled stays allocated, so that the id is never handed out twice
+
+    fn allocated(&mut self, lower: T, upper: T) -> Result<(), IdError> {
+        self.allocations += 1;
+
+        self.journal(JournalRecord::Allocated { lower, upper, cursor: self.strategy.cursor() });
+
+        self.notify(|observer| observer.on_allocate(lower, upper));
+
+        self.check_low_watermark();
+
+        self.check_journal()
+    }
+
+    fn freed(&mut self, lower: T, upper: T) {
+        self.advance_generations(lower, upper);
+
+        self.journal(JournalRecord::Freed { lower, upper });
+
+        if let Some(leak_detection) = &mut self.leak_detection {
+            leak_detection.freed(lower, upper);
+        }
+
+        let owned: Vec<T> = self.owners.range(lower..=upper).map(|(id, _)| *id).collect();
+
+        for id in owned {
+            self.owners.remove(&id);
+        }
+
+        let leased: Vec<T> = self.leases.range(lower..=upper).map(|(id, _)| *id).collect();
+
+        for id in leased {
+            self.leases.remove(&id);
+        }
+
+        self.notify(|observer| observer.on_free(lower, upper));
+
+        self.check_low_watermark();
+    }
+
+    fn exhausted(&self) -> IdError {
+        self.notify(|observer| observer.on_exhausted());
+
+        IdError::Exhausted
+    }
+
+    // a search that can miss while there are still free ids only counts as exhaustion if there
+    // are none left at all
+
+    fn exhausted_or(&self, error: IdError) -> IdError {
+        if self.free_ids.is_empty() {
+            self.exhausted()
+        } else {
+            error
+        }
+    }
+
+    fn check_low_watermark(&mut self) {
+        let watermark = match self.low_watermark {
+            Some(watermark) => watermark,
+            None => return,
+        };
+
+        // a free count of None is 2^128, which is above any watermark
+
+        match self.free_count() {
+            Some(free_count) if free_count <= watermark => {
+                if !self.below_low_watermark {
+                    self.below_low_watermark = true;
+
+                    self.notify(|observer| observer.on_low_watermark(free_count));
+                }
+            }
+            _ => self.below_low_watermark = false,
+        }
+    }
+
+    fn generation_of(&self, id: T) -> u32 {
+        match &self.generations {
+            Some(generations) => generations.generation_of(id),
+            None => 0,
+        }
+    }
+
+    fn advance_generations(&mut self, lower: T, upper: T) {
+        if let Some(generations) = &mut self.generations {
+            generations.advance(lower, upper);
+        }
+    }
+
+
+    fn is_quarantined(&self, lower: T, upper: T) -> bool {
+        match &self.quarantine {
+            Some(quarantine) => quarantine.contains_any(lower, upper),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::allocation_strategy::FastStrategy;
+    use crate::clock::ManualClock;
+    use crate::reuse_policy::ReusePolicy::ReuseFast;
+    use crate::reuse_policy::ReusePolicy::ReuseFastHighest;
+    use crate::reuse_policy::ReusePolicy::ReuseOldestFreed;
+    use crate::reuse_policy::ReusePolicy::ReuseRandom;
+    use crate::reuse_policy::ReusePolicy::ReuseSlow;
+    use crate::reuse_policy::ReusePolicy::ReuseSlowDescending;
+
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_new_for_all_supported_types() {
+        {
+            let manager = IdManager::<u8>::new(ReuseFast);
+
+            assert_eq!(manager.dump(), "[0,255]");
+        }
+        {
+            let manager = IdManager::<u16>::new(ReuseFast);
+
+            assert_eq!(manager.dump(), "[0,65535]");
+        }
+        {
+            let manager = IdManager::<u32>::new(ReuseFast);
+
+            assert_eq!(manager.dump(), "[0,4294967295]");
+        }
+        {
+            let manager = IdManager::<u64>::new(ReuseFast);
+
+            assert_eq!(manager.dump(), "[0,18446744073709551615]");
+        }
+        {
+            let manager = IdManager::<u128>::new(ReuseFast);
+
+            assert_eq!(manager.dump(), "[0,340282366920938463463374607431768211455]");
+        }
+        {
+            let manager = IdManager::<usize>::new(ReuseFast);
+
+            assert_eq!(manager.dump(), "[0,18446744073709551615]");
+        }
+    }
+
+    #[test]
+    fn test_new_limited_range() {
+        let manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        assert_eq!(manager.dump(), "[10,50]");
+    }
+
+    #[test]
+    fn test_can_allocate() {
+        let manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.can_allocate(), true);
+    }
+
+    #[test]
+    fn test_allocate() {
+        let mut manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.allocate(), 0);
+
+        assert_eq!(manager.dump(), "[1,255]");
+    }
+
+    #[test]
+    fn test_try_allocate() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 11);
+
+        assert_eq!(manager.try_allocate(), Ok(10));
+        assert_eq!(manager.try_allocate(), Ok(11));
+        assert_eq!(manager.try_allocate(), Err(IdError::Exhausted));
+
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    #[should_panic(expected = "No Ids available")]
+    fn test_allocate_when_exhausted() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 10);
+
+        assert_eq!(manager.allocate(), 10);
+
+        manager.allocate();
+    }
+
+    #[test]
+    fn test_allocate_all_ids_and_wrap() {
+        let mut manager = IdManager::<u8>::new(ReuseSlow);
+
+        for i in 0..u8::MAX {
+            assert_eq!(manager.allocate(), i);
+        }
+        assert_eq!(manager.dump(), "[255]");
+
+        assert_eq!(manager.allocate(), 255);
+
+        assert_eq!(manager.can_allocate(), false);
+
+        assert_eq!(manager.dump(), "");
+
+        for i in 0..10 {
+            manager.free(i);
+        }
+
+        assert_eq!(manager.dump(), "[0,9]");
+
+        for i in 0..10 {
+            assert_eq!(manager.allocate(), i);
+        }
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    fn test_allocate_all_ids_and_wrap_limited_range() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+
+        for i in 10..50 {
+            assert_eq!(manager.allocate(), i);
+        }
+        assert_eq!(manager.dump(), "[50]");
+
+        assert_eq!(manager.allocate(), 50);
+
+        assert_eq!(manager.can_allocate(), false);
+
+        assert_eq!(manager.dump(), "");
+
+        for i in 10..20 {
+            manager.free(i);
+        }
+
+        assert_eq!(manager.dump(), "[10,19]");
+
+        for i in 10..20 {
+            assert_eq!(manager.allocate(), i);
+        }
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    fn test_free() {
+        let mut manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.allocate(), 0);
+
+        assert_eq!(manager.dump(), "[1,255]");
+
+        manager.free(0);
+
+        assert_eq!(manager.dump(), "[0,255]");
+    }
+
+    #[test]
+    #[should_panic(expected = "id is not currently allocated")]
+    fn test_free_id_not_allocated() {
+        let mut manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.dump(), "[0,255]");
+
+        manager.free(0);
+
+        assert_eq!(manager.dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_try_free() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        assert_eq!(manager.allocate(), 10);
+
+        assert_eq!(manager.try_free(10), Ok(()));
+
+        assert_eq!(manager.dump(), "[10,50]");
+
+        assert_eq!(manager.try_free(10), Err(IdError::NotAllocated));
+
+        assert_eq!(manager.try_free(9), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.try_free(51), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.dump(), "[10,50]");
+    }
+
+    #[test]
+    fn test_try_allocate_specific() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+
+        assert_eq!(manager.try_allocate_specific(20), Ok(20));
+
+        assert_eq!(manager.dump(), "[10,19], [21,50]");
+
+        assert_eq!(manager.try_allocate_specific(20), Err(IdError::AlreadyAllocated));
+
+        assert_eq!(manager.try_allocate_specific(9), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.try_allocate_specific(51), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.allocate(), 10);
+
+        manager.free(20);
+
+        assert_eq!(manager.dump(), "[11,50]");
+    }
+
+    #[test]
+    #[should_panic(expected = "id is already allocated")]
+    fn test_allocate_specific_already_allocated() {
+        let mut manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.allocate(), 0);
+
+        manager.allocate_specific(0);
+    }
+
+    #[test]
+    fn test_allocate_near() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        manager.mark_interval_as_used(20, 29);
+
+        assert_eq!(manager.allocate_near(25, Direction::AtOrAbove), 30);
+        assert_eq!(manager.allocate_near(25, Direction::AtOrBelow), 19);
+        assert_eq!(manager.allocate_near(25, Direction::Nearest), 31);
+        assert_eq!(manager.allocate_near(24, Direction::Nearest), 18);
+        assert_eq!(manager.allocate_near(5, Direction::Nearest), 10);
+        assert_eq!(manager.allocate_near(255, Direction::Nearest), 50);
+        assert_eq!(manager.allocate_near(40, Direction::Nearest), 40);
+
+        assert_eq!(manager.dump(), "[11,17], [32,39], [41,49]");
+    }
+
+    #[test]
+    fn test_try_allocate_near() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        manager.mark_interval_as_used(20, 50);
+
+        assert_eq!(manager.try_allocate_near(25, Direction::AtOrAbove), Err(IdError::NoFreeIdInDirection));
+        assert_eq!(manager.try_allocate_near(5, Direction::AtOrBelow), Err(IdError::NoFreeIdInDirection));
+        assert_eq!(manager.try_allocate_near(25, Direction::AtOrBelow), Ok(19));
+
+        manager.mark_interval_as_used(10, 18);
+
+        assert_eq!(manager.try_allocate_near(25, Direction::Nearest), Err(IdError::Exhausted));
+    }
+
+    #[test]
+    fn test_allocate_block_reuse_fast() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        manager.mark_value_as_used(12);
+
+        assert_eq!(manager.allocate_block(2).dump(), "[10,11]");
+
+        assert_eq!(manager.dump(), "[13,50]");
+
+        assert_eq!(manager.allocate_block(5).dump(), "[13,17]");
+
+        assert_eq!(manager.dump(), "[18,50]");
+
+        manager.free(10);
+        manager.free(11);
+
+        assert_eq!(manager.allocate_block(2).dump(), "[10,11]");
+
+        assert_eq!(manager.allocate_block(3).dump(), "[18,20]");
+
+        assert_eq!(manager.dump(), "[21,50]");
+    }
+
+    #[test]
+    fn test_allocate_block_reuse_slow() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+
+        assert_eq!(manager.allocate_block(5).dump(), "[10,14]");
+
+        for id in 10..=14 {
+            manager.free(id);
+        }
+
+        assert_eq!(manager.allocate_block(5).dump(), "[15,19]");
+
+        assert_eq!(manager.allocate_block(30).dump(), "[20,49]");
+
+        assert_eq!(manager.dump(), "[10,14], [50]");
+
+        assert_eq!(manager.allocate_block(2).dump(), "[10,11]");
+
+        assert_eq!(manager.allocate(), 12);
+
+        assert_eq!(manager.dump(), "[13,14], [50]");
+    }
+
+    #[test]
+    fn test_try_allocate_block() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 19);
+
+        assert_eq!(manager.try_allocate_block(0), Err(IdError::InvalidCount));
+
+        assert_eq!(manager.try_allocate_block(11), Err(IdError::NoSuitableBlock));
+
+        assert_eq!(manager.try_allocate_block(10).unwrap().dump(), "[10,19]");
+
+        assert_eq!(manager.try_allocate_block(1), Err(IdError::Exhausted));
+
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    fn test_allocate_aligned() {
+        let mut manager = IdManager::<u16>::new_limited_range(ReuseFast, 10, 1000);
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[64,127]");
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[128,191]");
+
+        assert_eq!(manager.allocate_aligned(8, 16).dump(), "[16,23]");
+
+        assert_eq!(manager.dump(), "[10,15], [24,63], [192,1000]");
+
+        for id in 64..=127 {
+            manager.free(id);
+        }
+
+        assert_eq!(manager.allocate_aligned(32, 32).dump(), "[32,63]");
+
+        assert_eq!(manager.allocate_aligned(32, 32).dump(), "[64,95]");
+    }
+
+    #[test]
+    fn test_allocate_aligned_reuse_slow() {
+        let mut manager = IdManager::<u16>::new_limited_range(ReuseSlow, 0, 255);
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[0,63]");
+
+        for id in 0..=63 {
+            manager.free(id);
+        }
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[64,127]");
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[128,191]");
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[192,255]");
+
+        assert_eq!(manager.allocate_aligned(64, 64).dump(), "[0,63]");
+
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    fn test_try_allocate_aligned() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 1, 126);
+
+        assert_eq!(manager.try_allocate_aligned(0, 8), Err(IdError::InvalidCount));
+
+        assert_eq!(manager.try_allocate_aligned(8, 0), Err(IdError::InvalidAlignment));
+
+        assert_eq!(manager.try_allocate_aligned(64, 64), Err(IdError::NoSuitableBlock));
+
+        assert_eq!(manager.try_allocate_aligned(63, 64), Ok(Interval::new(64, 126)));
+
+        assert_eq!(manager.dump(), "[1,63]");
+    }
+
+    #[test]
+    fn test_try_free_interval() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        manager.mark_interval_as_used(10, 20);
+
+        assert_eq!(manager.try_free_interval(9, 12), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.try_free_interval(12, 11), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.try_free_interval(15, 21), Err(IdError::NotAllocated));
+
+        assert_eq!(manager.dump(), "[21,50]");
+
+        assert_eq!(manager.try_free_interval(12, 14), Ok(()));
+
+        assert_eq!(manager.dump(), "[12,14], [21,50]");
+
+        assert_eq!(manager.try_free_interval(15, 20), Ok(()));
+
+        assert_eq!(manager.dump(), "[12,50]");
+    }
+
+    #[test]
+    fn test_reuse_fast() {
+        let mut manager = IdManager::<u8>::new(ReuseFast);
+
+        for i in 0..10 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        assert_eq!(manager.dump(), "[10,255]");
+
+        manager.free(2);
+        manager.free(6);
+        manager.free(7);
+        manager.free(4);
+
+        assert_eq!(manager.dump(), "[2], [4], [6,7], [10,255]");
+
+        assert_eq!(manager.allocate(), 2);
+        assert_eq!(manager.allocate(), 4);
+        assert_eq!(manager.allocate(), 6);
+        assert_eq!(manager.allocate(), 7);
+        assert_eq!(manager.allocate(), 10);
+
+        assert_eq!(manager.dump(), "[11,255]");
+    }
+
+    #[test]
+    fn test_reuse_slow() {
+        let mut manager = IdManager::<u8>::new(ReuseSlow);
+
+        for i in 0..10 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        assert_eq!(manager.dump(), "[10,255]");
+
+        manager.free(2);
+        manager.free(6);
+        manager.free(7);
+        manager.free(4);
+
+        assert_eq!(manager.dump(), "[2], [4], [6,7], [10,255]");
+
+        for i in 10..255 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        assert_eq!(manager.dump(), "[2], [4], [6,7], [255]");
+
+        assert_eq!(manager.allocate(), 255);
+
+        assert_eq!(manager.allocate(), 2);
+        assert_eq!(manager.allocate(), 4);
+        assert_eq!(manager.allocate(), 6);
+        assert_eq!(manager.allocate(), 7);
+
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    fn test_reuse_fast_highest() {
+        let mut manager = IdManager::<u8>::new(ReuseFastHighest);
+
+        for i in (246..=255).rev() {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        assert_eq!(manager.dump(), "[0,245]");
+
+        manager.free(250);
+        manager.free(248);
+
+        assert_eq!(manager.allocate(), 250);
+        assert_eq!(manager.allocate(), 248);
+        assert_eq!(manager.allocate(), 245);
+
+        assert_eq!(manager.allocate_block(5).dump(), "[240,244]");
+
+        assert_eq!(manager.dump(), "[0,239]");
+    }
+
+    #[test]
+    fn test_reuse_slow_descending() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlowDescending, 10, 50);
+
+        for i in (41..=50).rev() {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        manager.free(45);
+        manager.free(48);
+
+        assert_eq!(manager.dump(), "[10,40], [45], [48]");
+
+        for i in (10..=40).rev() {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        assert_eq!(manager.allocate(), 48);
+        assert_eq!(manager.allocate(), 45);
+
+        assert_eq!(manager.can_allocate(), false);
+    }
+
+    #[test]
+    fn test_reuse_oldest_freed() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseOldestFreed, 0, 19);
+
+        for i in 0..10 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        manager.free(5);
+        manager.free(2);
+        manager.free(7);
+
+        assert_eq!(manager.dump(), "[2], [5], [7], [10,19]");
+
+        for i in 10..20 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        manager.free(12);
+        manager.free(13);
+        manager.free(3);
+
+        assert_eq!(manager.allocate(), 5);
+        assert_eq!(manager.allocate(), 2);
+        assert_eq!(manager.allocate(), 7);
+        assert_eq!(manager.allocate(), 12);
+        assert_eq!(manager.allocate(), 13);
+        assert_eq!(manager.allocate(), 3);
+
+        assert_eq!(manager.can_allocate(), false);
+    }
+
+    #[test]
+    fn test_reuse_oldest_freed_when_almost_full() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseOldestFreed, 0, 9);
+
+        for i in 0..10 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        manager.free(3);
+        manager.free(1);
+
+        assert_eq!(manager.allocate(), 3);
+
+        manager.free(3);
+
+        assert_eq!(manager.allocate(), 1);
+        assert_eq!(manager.allocate(), 3);
+    }
+
+    #[test]
+    fn test_allocate_block_reuse_oldest_freed() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseOldestFreed, 0, 20);
+
+        for i in 0..5 {
+            assert_eq!(manager.allocate(), i);
+        }
+
+        for i in 0..5 {
+            manager.free(i);
+        }
+
+        // the ids that have never been allocated were freed first
+
+        assert_eq!(manager.allocate_block(3).dump(), "[5,7]");
+
+        manager.mark_interval_as_used(8, 20);
+
+        assert_eq!(manager.allocate_block(3).dump(), "[0,2]");
+
+        assert_eq!(manager.allocate(), 3);
+        assert_eq!(manager.allocate(), 4);
+
+        assert_eq!(manager.can_allocate(), false);
+    }
+
+    #[test]
+    fn test_reuse_oldest_freed_ignores_ids_marked_as_used() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseOldestFreed, 0, 9);
+
+        manager.mark_interval_as_used(0, 9);
+
+        manager.free(8);
+        manager.free(2);
+        manager.free(3);
+        manager.free(4);
+
+        manager.mark_value_as_used(2);
+        manager.mark_value_as_used(4);
+
+        assert_eq!(manager.allocate(), 8);
+        assert_eq!(manager.allocate(), 3);
+
+        assert_eq!(manager.can_allocate(), false);
+    }
+
+    #[test]
+    fn test_reuse_random() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseRandom { seed: Some(1) }, 10, 50);
+
+        let mut allocated = Intervals::<u8>::new();
+
+        for _ in 10..=50 {
+            assert_eq!(allocated.insert_value(manager.allocate()), true);
+        }
+
+        assert_eq!(allocated.dump(), "[10,50]");
+
+        assert_eq!(manager.can_allocate(), false);
+
+        manager.free(20);
+        manager.free(40);
+
+        let id = manager.allocate();
+
+        assert_eq!(id == 20 || id == 40, true);
+    }
+
+    #[test]
+    fn test_reuse_random_is_reproducible_with_seed() {
+        let mut manager1 = IdManager::<u64>::new(ReuseRandom { seed: Some(42) });
+        let mut manager2 = IdManager::<u64>::new(ReuseRandom { seed: Some(42) });
+
+        let ids: Vec<u64> = (0..10).map(|_| manager1.allocate()).collect();
+
+        for id in ids {
+            assert_eq!(manager2.allocate(), id);
+        }
+    }
+
+    #[test]
+    fn test_reuse_random_sparse_range() {
+        let mut manager = IdManager::<u128>::new(ReuseRandom { seed: Some(7) });
+
+        manager.mark_interval_as_used(0, u128::MAX - 2);
+
+        let id1 = manager.allocate();
+        let id2 = manager.allocate();
+
+        assert_eq!(id1 >= u128::MAX - 1 && id2 >= u128::MAX - 1 && id1 != id2, true);
+
+        assert_eq!(manager.can_allocate(), false);
+    }
+
+    #[test]
+    fn test_quarantine_for_allocations() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.set_quarantine(Some(QuarantinePeriod::Allocations(2)));
+
+        assert_eq!(manager.allocate(), 0);
+        assert_eq!(manager.allocate(), 1);
+
+        manager.free(0);
+
+        assert_eq!(manager.dump(), "[2,9] quarantined: [0]");
+
+        assert_eq!(manager.try_free(0), Err(IdError::NotAllocated));
+
+        assert_eq!(manager.try_allocate_specific(0), Err(IdError::Quarantined));
+
+        assert_eq!(manager.allocate(), 2);
+        assert_eq!(manager.allocate(), 3);
+
+        assert_eq!(manager.dump(), "[4,9] quarantined: [0]");
+
+        assert_eq!(manager.allocate(), 0);
+
+        assert_eq!(manager.dump(), "[4,9]");
+    }
+
+    #[test]
+    fn test_quarantine_for_duration() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 1);
+
+        let clock = ManualClock::new();
+
+        manager.set_clock(Box::new(clock.clone()));
+
+        manager.set_quarantine(Some(QuarantinePeriod::Duration(Duration::from_secs(30))));
+
+        assert_eq!(manager.allocate(), 0);
+        assert_eq!(manager.allocate(), 1);
+
+        manager.free(1);
+
+        assert_eq!(manager.dump(), "quarantined: [1]");
+
+        assert_eq!(manager.can_allocate(), false);
+
+        assert_eq!(manager.try_allocate(), Err(IdError::Exhausted));
+
+        clock.advance(Duration::from_secs(29));
+
+        assert_eq!(manager.can_allocate(), false);
+
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(manager.can_allocate(), true);
+
+        assert_eq!(manager.allocate(), 1);
+    }
+
+    #[test]
+    fn test_flush_quarantine() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.set_quarantine(Some(QuarantinePeriod::Allocations(100)));
+
+        manager.mark_interval_as_used(0, 9);
+
+        manager.free(2);
+        manager.free(3);
+        manager.free(4);
+        manager.free(7);
+
+        assert_eq!(manager.dump(), "quarantined: [2,4], [7]");
+
+        manager.flush_quarantine();
+
+        assert_eq!(manager.dump(), "[2,4], [7]");
+
+        assert_eq!(manager.allocate(), 2);
+    }
+
+    #[test]
+    fn test_mark_value_as_used_removes_from_quarantine() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.set_quarantine(Some(QuarantinePeriod::Allocations(1)));
+
+        assert_eq!(manager.allocate(), 0);
+
+        manager.free(0);
+
+        manager.mark_value_as_used(0);
+
+        assert_eq!(manager.dump(), "[1,9]");
+
+        assert_eq!(manager.allocate(), 1);
+        assert_eq!(manager.allocate(), 2);
+
+        assert_eq!(manager.dump(), "[3,9]");
+    }
+
+    #[test]
+    fn test_disable_quarantine_releases_ids() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.set_quarantine(Some(QuarantinePeriod::Allocations(10)));
+
+        assert_eq!(manager.allocate(), 0);
+
+        manager.free(0);
+
+        manager.set_quarantine(None);
+
+        assert_eq!(manager.dump(), "[0,9]");
+
+        assert_eq!(manager.allocate(), 0);
+
+        manager.free(0);
+
+        assert_eq!(manager.dump(), "[0,9]");
+    }
+
+    struct HighestFirst;
+
+    impl AllocationStrategy<u8> for HighestFirst {
+        fn allocate(&mut self, free_ids: &Intervals<u8>) -> Option<u8> {
+            free_ids.last_value_at_or_before(u8::MAX)
+        }
+    }
+
+    #[test]
+    fn test_generations_disabled() {
+        let mut id_manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(id_manager.try_allocate_generational(), Err(IdError::GenerationsDisabled));
+
+        assert_eq!(id_manager.try_free_generational(GenerationalId::new(0, 0)), Err(IdError::GenerationsDisabled));
+
+        assert_eq!(id_manager.is_current(GenerationalId::new(0, 0)), false);
+    }
+
+    #[test]
+    fn test_allocate_generational() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        id_manager.enable_generations();
+
+        let id1 = id_manager.allocate_generational();
+
+        assert_eq!(id1, GenerationalId::new(0, 0));
+
+        assert_eq!(id_manager.is_current(id1), true);
+
+        id_manager.free_generational(id1);
+
+        assert_eq!(id_manager.is_current(id1), false);
+
+        let id2 = id_manager.allocate_generational();
+
+        assert_eq!(id2, GenerationalId::new(0, 1));
+
+        assert_eq!(id_manager.is_current(id1), false);
+
+        assert_eq!(id_manager.is_current(id2), true);
+
+        assert_eq!(id_manager.try_free_generational(id1), Err(IdError::StaleGeneration));
+
+        assert_eq!(id_manager.dump(), "[1,9]");
+
+        id_manager.free_generational(id2);
+
+        assert_eq!(id_manager.try_free_generational(id2), Err(IdError::StaleGeneration));
+
+        assert_eq!(id_manager.dump(), "[0,9]");
+    }
+
+    #[test]
+    fn test_free_block_advances_generations() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        id_manager.enable_generations();
+
+        let block = id_manager.allocate_block(3);
+
+        for id in block.lower()..=block.upper() {
+            id_manager.free(id);
+        }
+
+        assert_eq!(id_manager.allocate_generational(), GenerationalId::new(0, 1));
+
+        assert_eq!(id_manager.allocate_generational(), GenerationalId::new(1, 1));
+
+        assert_eq!(id_manager.allocate_generational(), GenerationalId::new(2, 1));
+
+        assert_eq!(id_manager.allocate_generational(), GenerationalId::new(3, 0));
+    }
+
+    #[test]
+    fn test_generations_with_quarantine() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        id_manager.enable_generations();
+
+        id_manager.set_quarantine(Some(QuarantinePeriod::Allocations(1)));
+
+        let id1 = id_manager.allocate_generational();
+
+        id_manager.free_generational(id1);
+
+        assert_eq!(id_manager.is_current(id1), false);
+
+        assert_eq!(id_manager.try_free_generational(id1), Err(IdError::StaleGeneration));
+    }
+
+    #[test]
+    #[should_panic(expected = "id generation is stale")]
+    fn test_free_stale_generation() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        id_manager.enable_generations();
+
+        let id1 = id_manager.allocate_generational();
+
+        id_manager.free_generational(id1);
+
+        id_manager.free_generational(id1);
+    }
+
+    #[test]
+    fn test_new_with_ranges() {
+        let id_manager = IdManager::<u16>::new_with_ranges(ReuseSlow, &[100..=999, 5000..=5999]);
+
+        assert_eq!(id_manager.dump(), "[100,999], [5000,5999]");
+    }
+
+    #[test]
+    fn test_new_with_overlapping_ranges() {
+        let id_manager = IdManager::<u8>::new_with_ranges(ReuseSlow, &[10..=20, 15..=30, 40..=40]);
+
+        assert_eq!(id_manager.dump(), "[10,30], [40]");
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one range is required")]
+    fn test_new_with_no_ranges() {
+        let _id_manager = IdManager::<u8>::new_with_ranges(ReuseSlow, &[]);
+    }
+
+    #[test]
+    fn test_ranges_reuse_slow_wraps_across_gaps() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseSlow, &[10..=11, 20..=21]);
+
+        assert_eq!(id_manager.allocate(), 10);
+        assert_eq!(id_manager.allocate(), 11);
+        assert_eq!(id_manager.allocate(), 20);
+
+        id_manager.free(10);
+
+        assert_eq!(id_manager.allocate(), 21);
+        assert_eq!(id_manager.allocate(), 10);
+
+        assert_eq!(id_manager.try_allocate(), Err(IdError::Exhausted));
+    }
+
+    #[test]
+    fn test_ranges_reject_ids_in_gaps() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
+
+        assert_eq!(id_manager.try_allocate_specific(25), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.try_free(25), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.try_free_interval(15, 35), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.try_allocate_near(25, Direction::Nearest), Ok(30));
+    }
+
+    #[test]
+    fn test_ranges_extend_and_shrink() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
+
+        assert_eq!(id_manager.try_shrink_range(21, 25), Err(ShrinkRangeError::Failed(IdError::EmptyRange)));
+
+        assert_eq!(id_manager.allocate_specific(35), 35);
+
+        assert_eq!(id_manager.shrink_range(10, 29), Err(vec![Interval::new(35, 35)]));
+
+        assert_eq!(id_manager.shrink_range(15, 36), Ok(()));
+
+        assert_eq!(id_manager.dump(), "[15,19], [30,34], [36]");
+
+        id_manager.extend_range(12, 40);
+
+        assert_eq!(id_manager.dump(), "[12,19], [30,34], [36,40]");
+
+        assert_eq!(id_manager.try_allocate_specific(25), Err(IdError::OutOfRange));
+    }
+
+    #[test]
+    #[should_panic(expected = "new range must contain at least one id")]
+    fn test_shrink_range_into_gap() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
+
+        let _ = id_manager.shrink_range(21, 25);
+    }
+
+    #[test]
+    fn test_extend_range() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 12);
+
+        for i in 10..=12 {
+            assert_eq!(id_manager.allocate(), i);
+        }
+
+        assert_eq!(id_manager.can_allocate(), false);
+
+        id_manager.extend_range(5, 20);
+
+        assert_eq!(id_manager.dump(), "[5,9], [13,20]");
+
+        assert_eq!(id_manager.allocate(), 13);
+
+        assert_eq!(id_manager.allocate_specific(5), 5);
+
+        assert_eq!(id_manager.try_extend_range(6, 20), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.try_extend_range(5, 19), Err(IdError::OutOfRange));
+
+        id_manager.free(5);
+
+        assert_eq!(id_manager.dump(), "[5,9], [14,20]");
+    }
+
+    #[test]
+    fn test_shrink_range() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 19);
+
+        id_manager.allocate_specific(2);
+        id_manager.allocate_specific(3);
+        id_manager.allocate_specific(10);
+        id_manager.allocate_specific(18);
+
+        assert_eq!(id_manager.shrink_range(4, 15), Err(vec![Interval::new(2, 3), Interval::new(18, 18)]));
+
+        assert_eq!(id_manager.dump(), "[0,1], [4,9], [11,17], [19]");
+
+        id_manager.free(2);
+        id_manager.free(3);
+        id_manager.free(18);
+
+        assert_eq!(id_manager.shrink_range(4, 15), Ok(()));
+
+        assert_eq!(id_manager.dump(), "[4,9], [11,15]");
+
+        assert_eq!(id_manager.try_allocate_specific(3), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.try_free(16), Err(IdError::OutOfRange));
+
+        id_manager.extend_range(0, 19);
+
+        assert_eq!(id_manager.dump(), "[0,9], [11,19]");
+    }
+
+    #[test]
+    fn test_shrink_range_releases_quarantined_ids() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        id_manager.set_quarantine(Some(QuarantinePeriod::Allocations(10)));
+
+        id_manager.allocate_specific(8);
+        id_manager.allocate_specific(9);
+
+        id_manager.free(9);
+
+        assert_eq!(id_manager.shrink_range(0, 7), Err(vec![Interval::new(8, 8)]));
+
+        id_manager.free(8);
+
+        assert_eq!(id_manager.shrink_range(0, 7), Ok(()));
+
+        assert_eq!(id_manager.dump(), "[0,7]");
+    }
+
+    #[test]
+    #[should_panic(expected = "id out of range")]
+    fn test_shrink_range_outside_current_range() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 20);
+
+        let _ = id_manager.shrink_range(5, 15);
+    }
+
+    #[test]
+    fn test_try_shrink_range() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 39);
+
+        assert_eq!(id_manager.try_shrink_range(5, 15), Err(ShrinkRangeError::Failed(IdError::OutOfRange)));
+        assert_eq!(id_manager.try_shrink_range(15, 12), Err(ShrinkRangeError::Failed(IdError::OutOfRange)));
+
+        id_manager.allocate_specific(35);
+
+        assert_eq!(id_manager.try_shrink_range(10, 29), Err(ShrinkRangeError::Allocated(vec![Interval::new(35, 35)])));
+        assert_eq!(id_manager.try_shrink_range(10, 35), Ok(()));
+
+        assert_eq!(id_manager.dump(), "[10,34]");
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 19);
+
+        assert_eq!(id_manager.reserve(0, 1, "network"), Ok(()));
+
+        assert_eq!(id_manager.reserve(19, 19, "broadcast"), Ok(()));
+
+        assert_eq!(id_manager.dump(), "[2,18]");
+
+        assert_eq!(id_manager.reservations(), vec![Reservation::new(0, 1, "network"), Reservation::new(19, 19, "broadcast")]);
+
+        assert_eq!(id_manager.allocate(), 2);
+
+        assert_eq!(id_manager.try_free(1), Err(IdError::Reserved));
+
+        assert_eq!(id_manager.try_free_interval(0, 5), Err(IdError::Reserved));
+
+        assert_eq!(id_manager.try_allocate_specific(19), Err(IdError::Reserved));
+
+        id_manager.free(2);
+
+        assert_eq!(id_manager.dump(), "[2,18]");
+    }
+
+    #[test]
+    fn test_reserve_fails() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 19);
+
+        id_manager.set_quarantine(Some(QuarantinePeriod::Allocations(10)));
+
+        assert_eq!(id_manager.reserve(10, 20, "outside"), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.reserve(10, 5, "inverted"), Err(IdError::OutOfRange));
+
+        assert_eq!(id_manager.allocate_specific(5), 5);
+
+        assert_eq!(id_manager.reserve(0, 5, "allocated"), Err(IdError::AlreadyAllocated));
+
+        id_manager.free(5);
+
+        assert_eq!(id_manager.reserve(5, 5, "quarantined"), Err(IdError::Quarantined));
+
+        assert_eq!(id_manager.reserve(10, 12, "first"), Ok(()));
+
+        assert_eq!(id_manager.reserve(12, 14, "overlapping"), Err(IdError::Reserved));
+
+        assert_eq!(id_manager.reservations(), vec![Reservation::new(10, 12, "first")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "id is reserved")]
+    fn test_free_reserved_id() {
+        let mut id_manager = IdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(id_manager.reserve(0, 0, "reserved"), Ok(()));
+
+        id_manager.free(0);
+    }
+
+    #[test]
+    fn test_membership_queries() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
+
+        id_manager.set_quarantine(Some(QuarantinePeriod::Allocations(10)));
+
+        id_manager.mark_interval_as_used(10, 12);
+
+        assert_eq!(id_manager.reserve(13, 13, "reserved"), Ok(()));
+
+        id_manager.free(12);
+
+        assert_eq!(id_manager.is_allocated(11), true);
+        assert_eq!(id_manager.is_free(11), false);
+
+        assert_eq!(id_manager.is_allocated(12), false);
+        assert_eq!(id_manager.is_free(12), false);
+
+        assert_eq!(id_manager.is_allocated(13), false);
+        assert_eq!(id_manager.is_free(13), false);
+
+        assert_eq!(id_manager.is_allocated(14), false);
+        assert_eq!(id_manager.is_free(14), true);
+
+        assert_eq!(id_manager.is_allocated(25), false);
+        assert_eq!(id_manager.is_free(25), false);
 
-        self.free_ids.remove_interval(lower, upper);
+        assert_eq!(id_manager.next_free_at_or_after(0), Some(14));
+        assert_eq!(id_manager.next_free_at_or_after(15), Some(15));
+        assert_eq!(id_manager.next_free_at_or_after(20), Some(30));
+        assert_eq!(id_manager.next_free_at_or_after(40), None);
+
+        assert_eq!(id_manager.prev_free_at_or_before(255), Some(39));
+        assert_eq!(id_manager.prev_free_at_or_before(29), Some(19));
+        assert_eq!(id_manager.prev_free_at_or_before(13), None);
     }
 
-    fn increment_id(&self, mut id: T) -> T {
-        if id == self.max_id
-        {
-            id = self.min_id;
-        } else {
-            id = id + T::one();
-            //id += T::one();           // needs additional bounds
-        }
+    #[test]
+    fn test_statistics() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
 
-        id
-    }
+        assert_eq!(id_manager.capacity(), Some(20));
+        assert_eq!(id_manager.free_count(), Some(20));
+        assert_eq!(id_manager.allocated_count(), Some(0));
+        assert_eq!(id_manager.interval_count(), 2);
+        assert_eq!(id_manager.largest_free_block(), Some(Interval::new(10, 19)));
 
-    pub fn free(&mut self, id: T) {
-        if !self.free_ids.insert_value(id)
-        {
-            panic!("id is not currently allocated");
-        }
-    }
-}
+        id_manager.set_quarantine(Some(QuarantinePeriod::Allocations(10)));
 
-#[cfg(test)]
-mod tests {
-    use crate::reuse_policy::ReusePolicy::ReuseFast;
-    use crate::reuse_policy::ReusePolicy::ReuseSlow;
+        id_manager.mark_interval_as_used(12, 15);
 
-    use super::*;
+        assert_eq!(id_manager.reserve(30, 31, "reserved"), Ok(()));
 
-    #[test]
-    fn test_new() {
-        let manager = IdManager::<u8>::new(ReuseFast);
+        id_manager.free(15);
 
-        assert_eq!(manager.dump(), "[0,255]");
+        assert_eq!(id_manager.capacity(), Some(20));
+        assert_eq!(id_manager.free_count(), Some(14));
+        assert_eq!(id_manager.allocated_count(), Some(3));
+        assert_eq!(id_manager.interval_count(), 3);
+        assert_eq!(id_manager.largest_free_block(), Some(Interval::new(32, 39)));
     }
 
     #[test]
-    fn test_new_for_all_supported_types() {
-        {
-            let manager = IdManager::<u8>::new(ReuseFast);
+    fn test_statistics_full_u128_range() {
+        let mut id_manager = IdManager::<u128>::new(ReuseFast);
 
-            assert_eq!(manager.dump(), "[0,255]");
-        }
-        {
-            let manager = IdManager::<u16>::new(ReuseFast);
+        assert_eq!(id_manager.capacity(), None);
+        assert_eq!(id_manager.free_count(), None);
+        assert_eq!(id_manager.allocated_count(), Some(0));
+        assert_eq!(id_manager.largest_free_block(), Some(Interval::new(u128::MIN, u128::MAX)));
 
-            assert_eq!(manager.dump(), "[0,65535]");
-        }
-        {
-            let manager = IdManager::<u32>::new(ReuseFast);
+        assert_eq!(id_manager.allocate(), 0);
 
-            assert_eq!(manager.dump(), "[0,4294967295]");
-        }
-        {
-            let manager = IdManager::<u64>::new(ReuseFast);
+        assert_eq!(id_manager.free_count(), Some(u128::MAX));
+        assert_eq!(id_manager.allocated_count(), Some(1));
 
-            assert_eq!(manager.dump(), "[0,18446744073709551615]");
-        }
-        {
-            let manager = IdManager::<u128>::new(ReuseFast);
+        id_manager.mark_interval_as_used(1, u128::MAX - 1);
 
-            assert_eq!(manager.dump(), "[0,340282366920938463463374607431768211455]");
-        }
-        {
-            let manager = IdManager::<usize>::new(ReuseFast);
+        assert_eq!(id_manager.free_count(), Some(1));
+        assert_eq!(id_manager.allocated_count(), Some(u128::MAX));
 
-            assert_eq!(manager.dump(), "[0,18446744073709551615]");
-        }
-    }
+        id_manager.mark_value_as_used(u128::MAX);
 
-    #[test]
-    fn test_new_limited_range() {
-        let manager = IdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+        assert_eq!(id_manager.free_count(), Some(0));
+        assert_eq!(id_manager.allocated_count(), None);
+        assert_eq!(id_manager.largest_free_block(), None);
 
-        assert_eq!(manager.dump(), "[10,50]");
+        id_manager.free_interval(0, u128::MAX);
+
+        assert_eq!(id_manager.free_count(), None);
+        assert_eq!(id_manager.allocated_count(), Some(0));
     }
 
     #[test]
-    fn test_can_allocate() {
-        let manager = IdManager::<u8>::new(ReuseFast);
+    fn test_free_intervals() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
 
-        assert_eq!(manager.can_allocate(), true);
-    }
+        id_manager.mark_interval_as_used(12, 15);
 
-    #[test]
-    fn test_allocate() {
-        let mut manager = IdManager::<u8>::new(ReuseFast);
+        id_manager.mark_interval_as_used(30, 39);
 
-        assert_eq!(manager.allocate(), 0);
+        assert_eq!(id_manager.free_intervals().collect::<Vec<_>>(), vec![10..=11, 16..=19]);
 
-        assert_eq!(manager.dump(), "[1,255]");
+        assert_eq!(id_manager.free_values().collect::<Vec<_>>(), vec![10, 11, 16, 17, 18, 19]);
     }
 
     #[test]
-    fn test_allocate_all_ids_and_wrap() {
-        let mut manager = IdManager::<u8>::new(ReuseSlow);
+    fn test_allocated_intervals() {
+        let mut id_manager = IdManager::<u8>::new_with_ranges(ReuseFast, &[10..=19, 30..=39]);
 
-        for i in 0..u8::MAX {
-            assert_eq!(manager.allocate(), i);
-        }
-        assert_eq!(manager.dump(), "[255]");
+        assert_eq!(id_manager.allocated_intervals().next(), None);
 
-        assert_eq!(manager.allocate(), 255);
+        id_manager.set_quarantine(Some(QuarantinePeriod::Allocations(10)));
 
-        assert_eq!(manager.can_allocate(), false);
+        id_manager.mark_interval_as_used(10, 15);
 
-        assert_eq!(manager.dump(), "");
+        id_manager.mark_interval_as_used(35, 39);
 
-        for i in 0..10 {
-            manager.free(i);
-        }
+        assert_eq!(id_manager.reserve(37, 37, "reserved"), Err(IdError::AlreadyAllocated));
 
-        assert_eq!(manager.dump(), "[0,9]");
+        assert_eq!(id_manager.reserve(19, 19, "reserved"), Ok(()));
 
-        for i in 0..10 {
-            assert_eq!(manager.allocate(), i);
-        }
-        assert_eq!(manager.dump(), "");
+        id_manager.free(12);
+
+        id_manager.free(38);
+
+        assert_eq!(id_manager.allocated_intervals().collect::<Vec<_>>(), vec![10..=11, 13..=15, 35..=37, 39..=39]);
+
+        assert_eq!(id_manager.allocated_values().collect::<Vec<_>>(), vec![10, 11, 13, 14, 15, 35, 36, 37, 39]);
     }
 
     #[test]
-    fn test_allocate_all_ids_and_wrap_limited_range() {
-        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+    fn test_allocated_values_full_range() {
+        let mut id_manager = IdManager::<u8>::new(ReuseFast);
 
-        for i in 10..50 {
-            assert_eq!(manager.allocate(), i);
-        }
-        assert_eq!(manager.dump(), "[50]");
+        id_manager.mark_interval_as_used(u8::MIN, u8::MAX);
 
-        assert_eq!(manager.allocate(), 50);
+        assert_eq!(id_manager.allocated_intervals().collect::<Vec<_>>(), vec![u8::MIN..=u8::MAX]);
 
-        assert_eq!(manager.can_allocate(), false);
+        assert_eq!(id_manager.allocated_values().count(), 256);
 
-        assert_eq!(manager.dump(), "");
+        assert_eq!(id_manager.free_values().count(), 0);
+    }
 
-        for i in 10..20 {
-            manager.free(i);
-        }
+    struct CountingObserver {
+        allocations: AtomicUsize,
+        low_watermarks: AtomicUsize,
+    }
 
-        assert_eq!(manager.dump(), "[10,19]");
+    impl IdManagerObserver<u8> for CountingObserver {
+        fn on_allocate(&self, _lower: u8, _upper: u8) {
+            self.allocations.fetch_add(1, Ordering::SeqCst);
+        }
 
-        for i in 10..20 {
-            assert_eq!(manager.allocate(), i);
+        fn on_low_watermark(&self, _free_count: u128) {
+            self.low_watermarks.fetch_add(1, Ordering::SeqCst);
         }
-        assert_eq!(manager.dump(), "");
     }
 
     #[test]
-    fn test_free() {
-        let mut manager = IdManager::<u8>::new(ReuseFast);
+    fn test_low_watermark() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
 
-        assert_eq!(manager.allocate(), 0);
+        let observer = Arc::new(CountingObserver { allocations: Default::default(), low_watermarks: Default::default() });
 
-        assert_eq!(manager.dump(), "[1,255]");
+        id_manager.add_observer(observer.clone());
 
-        manager.free(0);
+        id_manager.set_low_watermark(Some(5));
 
-        assert_eq!(manager.dump(), "[0,255]");
+        for _ in 0..5 {
+            id_manager.allocate();
+        }
+
+        assert_eq!(observer.low_watermarks.load(Ordering::SeqCst), 1);
+
+        id_manager.allocate();
+
+        assert_eq!(observer.low_watermarks.load(Ordering::SeqCst), 1);
+
+        id_manager.free_interval(0, 1);
+
+        assert_eq!(observer.low_watermarks.load(Ordering::SeqCst), 1);
+
+        id_manager.allocate_specific(0);
+
+        assert_eq!(observer.low_watermarks.load(Ordering::SeqCst), 2);
+
+        id_manager.allocate_near(0, Direction::AtOrAbove);
+
+        assert_eq!(observer.low_watermarks.load(Ordering::SeqCst), 2);
+
+        assert_eq!(observer.allocations.load(Ordering::SeqCst), 8);
     }
 
     #[test]
-    #[should_panic(expected = "id is not currently allocated")]
-    fn test_free_id_not_allocated() {
-        let mut manager = IdManager::<u8>::new(ReuseFast);
+    fn test_low_watermark_when_already_low() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
 
-        assert_eq!(manager.dump(), "[0,255]");
+        let observer = Arc::new(CountingObserver { allocations: Default::default(), low_watermarks: Default::default() });
 
-        manager.free(0);
+        id_manager.add_observer(observer.clone());
 
-        assert_eq!(manager.dump(), "[0,255]");
+        id_manager.mark_interval_as_used(0, 8);
+
+        id_manager.set_low_watermark(Some(1));
+
+        assert_eq!(observer.low_watermarks.load(Ordering::SeqCst), 1);
     }
 
     #[test]
-    fn test_reuse_fast() {
-        let mut manager = IdManager::<u8>::new(ReuseFast);
+    fn test_custom_strategy() {
+        let mut manager = IdManager::<u8>::new_limited_range(Box::new(HighestFirst), 10, 50);
 
-        for i in 0..10 {
-            assert_eq!(manager.allocate(), i);
-        }
+        assert_eq!(manager.allocate(), 50);
+        assert_eq!(manager.allocate(), 49);
 
-        assert_eq!(manager.dump(), "[10,255]");
+        manager.free(50);
 
-        manager.free(2);
-        manager.free(6);
-        manager.free(7);
-        manager.free(4);
+        assert_eq!(manager.allocate(), 50);
 
-        assert_eq!(manager.dump(), "[2], [4], [6,7], [10,255]");
+        assert_eq!(manager.allocate_block(2).dump(), "[10,11]");
 
-        assert_eq!(manager.allocate(), 2);
-        assert_eq!(manager.allocate(), 4);
-        assert_eq!(manager.allocate(), 6);
-        assert_eq!(manager.allocate(), 7);
-        assert_eq!(manager.allocate(), 10);
+        assert_eq!(manager.dump(), "[12,48]");
+    }
 
-        assert_eq!(manager.dump(), "[11,255]");
+    struct NotFree;
+
+    impl AllocationStrategy<u8> for NotFree {
+        fn allocate(&mut self, _free_ids: &Intervals<u8>) -> Option<u8> {
+            Some(0)
+        }
     }
 
     #[test]
-    fn test_reuse_slow() {
-        let mut manager = IdManager::<u8>::new(ReuseSlow);
+    fn test_custom_strategy_that_chooses_an_id_that_is_not_free() {
+        let mut manager = IdManager::<u8>::new_limited_range(Box::new(NotFree), 10, 50);
 
-        for i in 0..10 {
-            assert_eq!(manager.allocate(), i);
-        }
+        assert_eq!(manager.try_allocate(), Err(IdError::InvalidStrategyChoice));
 
-        assert_eq!(manager.dump(), "[10,255]");
+        assert_eq!(manager.dump(), "[10,50]");
+    }
 
-        manager.free(2);
-        manager.free(6);
-        manager.free(7);
-        manager.free(4);
+    struct PastTheEnd;
 
-        assert_eq!(manager.dump(), "[2], [4], [6,7], [10,255]");
+    impl AllocationStrategy<u8> for PastTheEnd {
+        fn allocate(&mut self, free_ids: &Intervals<u8>) -> Option<u8> {
+            free_ids.first_value_at_or_after(0)
+        }
 
-        for i in 10..255 {
-            assert_eq!(manager.allocate(), i);
+        fn allocate_block(&mut self, _free_ids: &Intervals<u8>, _count: u8, _alignment: u8) -> Option<u8> {
+            Some(250)
         }
+    }
 
-        assert_eq!(manager.dump(), "[2], [4], [6,7], [255]");
+    #[test]
+    fn test_custom_strategy_that_chooses_a_block_that_is_not_free() {
+        let mut manager = IdManager::<u8>::new(Box::new(PastTheEnd));
 
-        assert_eq!(manager.allocate(), 255);
+        assert_eq!(manager.try_allocate_block(10), Err(IdError::InvalidStrategyChoice));
 
-        assert_eq!(manager.allocate(), 2);
-        assert_eq!(manager.allocate(), 4);
-        assert_eq!(manager.allocate(), 6);
-        assert_eq!(manager.allocate(), 7);
+        manager.mark_value_as_used(252);
 
-        assert_eq!(manager.dump(), "");
+        assert_eq!(manager.try_allocate_block(4), Err(IdError::InvalidStrategyChoice));
+
+        assert_eq!(manager.try_allocate_block(2), Ok(Interval::new(250, 251)));
     }
 
     #[test]
@@ -374,4 +2529,152 @@ mod tests {
 
         assert_eq!(manager.dump(), "");
     }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 0, 9);
+
+        manager.set_quarantine(Some(QuarantinePeriod::Allocations(2)));
+
+        assert_eq!(manager.allocate(), 0);
+        assert_eq!(manager.allocate(), 1);
+
+        manager.free(0);
+
+        let state = manager.to_state().unwrap();
+
+        assert_eq!(state.next_to_allocate, Some(2));
+
+        assert_eq!(state.free_ids, vec![Interval::new(0, 0), Interval::new(2, 9)]);
+
+        let mut restored = IdManager::<u8>::from_state(state).unwrap();
+
+        assert_eq!(restored.dump(), "[0], [2,9]");
+
+        assert_eq!(restored.allocate(), 2);
+
+        assert_eq!(IdManager::<u8>::new(Box::new(FastStrategy)).to_state().err(), Some(StateError::CustomStrategy));
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut manager = IdManager::<u16>::new_with_ranges(ReuseSlowDescending, &[0..=99, 1000..=1999]);
+
+        for _ in 0..10 {
+            manager.allocate();
+        }
+
+        manager.free(1995);
+
+        manager.reserve(0, 9, "hardware").unwrap();
+
+        let mut buffer = Vec::new();
+
+        manager.write_snapshot(&mut buffer).unwrap();
+
+        let mut restored = IdManager::<u16>::read_snapshot(&buffer[..]).unwrap();
+
+        assert_eq!(restored.dump(), manager.dump());
+
+        assert_eq!(restored.reservations(), manager.reservations());
+
+        assert_eq!(restored.allocate(), manager.allocate());
+
+        buffer.truncate(buffer.len() - 1);
+
+        assert!(matches!(IdManager::<u16>::read_snapshot(&buffer[..]), Err(SnapshotError::Truncated)));
+    }
+
+    #[test]
+    fn test_failed_journal_blocks_allocation_until_compacted() {
+        let path = std::env::temp_dir().join(format!("id-manager-failed-journal-{}.journal", std::process::id()));
+
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.enable_journal(&path).unwrap();
+
+        assert_eq!(manager.allocate(), 0);
+
+        manager.journal.as_mut().unwrap().mark_failed();
+
+        assert_eq!(manager.try_allocate(), Err(IdError::JournalFailed));
+        assert_eq!(manager.try_allocate_specific(5), Err(IdError::JournalFailed));
+        assert_eq!(manager.try_allocate_block(2), Err(IdError::JournalFailed));
+
+        manager.free(0);
+
+        manager.compact_journal().unwrap();
+
+        assert_eq!(manager.allocate(), 0);
+
+        assert_eq!(IdManager::<u8>::recover(&path).unwrap().dump(), "[1,9]");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_failed_journal_blocks_marking_until_compacted() {
+        let path = std::env::temp_dir().join(format!("id-manager-failed-journal-mark-{}.journal", std::process::id()));
+
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.enable_journal(&path).unwrap();
+
+        manager.journal.as_mut().unwrap().mark_failed();
+
+        assert_eq!(manager.try_mark_value_as_used(5), Err(IdError::JournalFailed));
+        assert_eq!(manager.try_mark_interval_as_used(2, 4), Err(IdError::JournalFailed));
+
+        assert_eq!(manager.dump(), "[0,9]");
+
+        manager.compact_journal().unwrap();
+
+        assert_eq!(manager.try_mark_interval_as_used(2, 4), Ok(()));
+
+        assert_eq!(IdManager::<u8>::recover(&path).unwrap().dump(), "[0,1], [5,9]");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_after_marking_across_a_gap() {
+        let path = std::env::temp_dir().join(format!("id-manager-mark-across-gap-{}.journal", std::process::id()));
+
+        let mut manager = IdManager::<u16>::new_with_ranges(ReuseFast, &[100..=199, 500..=599]);
+
+        manager.enable_journal(&path).unwrap();
+
+        manager.mark_interval_as_used(150, 550);
+
+        manager.mark_value_as_used(100);
+
+        assert_eq!(manager.dump(), "[101,149], [551,599]");
+
+        assert_eq!(IdManager::<u16>::recover(&path).unwrap().dump(), "[101,149], [551,599]");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "id out of range")]
+    fn test_mark_value_as_used_out_of_range() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 19);
+
+        manager.mark_value_as_used(20);
+    }
+
+    #[test]
+    fn test_try_mark_interval_as_used_out_of_range() {
+        let mut manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 19);
+
+        assert_eq!(manager.try_mark_value_as_used(9), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.try_mark_interval_as_used(5, 12), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.try_mark_interval_as_used(15, 25), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.dump(), "[10,19]");
+
+        assert_eq!(manager.try_mark_interval_as_used(10, 19), Ok(()));
+    }
 }
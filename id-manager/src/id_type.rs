@@ -1,4 +1,4 @@
-pub trait IdType where Self: Ord + std::ops::Add<Self, Output=Self> + std::ops::Sub<Self, Output=Self> + Sized + num::One + std::fmt::Display + Copy
+pub trait IdType where Self: Ord + std::ops::Add<Self, Output=Self> + std::ops::Sub<Self, Output=Self> + std::ops::Rem<Self, Output=Self> + Sized + num::Zero + num::One + num::ToPrimitive + num::FromPrimitive + std::fmt::Display + Copy + Send + 'static
 {
     const MAX: Self;
     const MIN: Self;
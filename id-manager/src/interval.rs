@@ -5,7 +5,7 @@ use num::One;
 
 use crate::id_type::IdType;
 
-#[derive(PartialOrd, Eq, PartialEq, Clone)]
+#[derive(Eq, PartialEq, Clone, Debug)]
 pub struct Interval<T: IdType> {
     lower: T,
     upper: T,
@@ -35,7 +35,7 @@ impl<T: IdType> Interval<T> {
         self.upper
     }
 
-    fn dump(&self) -> String {
+    pub fn dump(&self) -> String {
         format!("{}", self)
     }
 
@@ -78,6 +78,12 @@ impl<T: IdType> fmt::Display for Interval<T> {
     }
 }
 
+impl<T: IdType> PartialOrd for Interval<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl<T: IdType> Ord for Interval<T> {
     fn cmp(&self, other: &Self) -> Ordering {
         let lower_is = self.lower.cmp(&other.lower);
@@ -105,6 +111,7 @@ impl<T: IdType> Ord for Interval<T> {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
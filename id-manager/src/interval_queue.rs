@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use crate::id_type::IdType;
+use crate::interval::Interval;
+
+// a FIFO of disjoint intervals, each with a value, that can also have ids removed from the middle
+// of it in O(log n). the queue is keyed by the sequence in which intervals were pushed and there's
+// an index by lower value to find the intervals that hold an id. when ids are removed from an
+// interval the pieces that are left keep its place in the queue.
+
+pub struct IntervalQueue<T: IdType, V: Copy> {
+    // (sequence, lower) to (upper, value), lower is part of the key as pieces share a sequence
+    queue: BTreeMap<(u64, T), (T, V)>,
+    // lower to (upper, sequence)
+    index: BTreeMap<T, (T, u64)>,
+    next_sequence: u64,
+}
+
+impl<T: IdType, V: Copy> IntervalQueue<T, V> {
+    pub fn new() -> Self {
+        IntervalQueue { queue: BTreeMap::new(), index: BTreeMap::new(), next_sequence: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn front(&self) -> Option<(Interval<T>, V)> {
+        self.queue.iter().next().map(|(&(_, lower), &(upper, value))| (Interval::new(lower, upper), value))
+    }
+
+    pub fn back(&self) -> Option<(Interval<T>, V)> {
+        self.queue.iter().next_back().map(|(&(_, lower), &(upper, value))| (Interval::new(lower, upper), value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Interval<T>, V)> + '_ {
+        self.queue.iter().map(|(&(_, lower), &(upper, value))| (Interval::new(lower, upper), value))
+    }
+
+    // the interval must not overlap anything already in the queue
+
+    pub fn push_back(&mut self, lower: T, upper: T, value: V) {
+        let sequence = self.next_sequence;
+
+        self.next_sequence += 1;
+
+        self.insert(sequence, lower, upper, value);
+    }
+
+    pub fn pop_front(&mut self) -> Option<(Interval<T>, V)> {
+        let (&(sequence, lower), &(upper, value)) = self.queue.iter().next()?;
+
+        self.queue.remove(&(sequence, lower));
+
+        self.index.remove(&lower);
+
+        Some((Interval::new(lower, upper), value))
+    }
+
+    pub fn remove(&mut self, lower: T, upper: T) {
+        let mut overlapping = Vec::new();
+
+        if let Some((&entry_lower, &(entry_upper, sequence))) = self.index.range(..lower).next_back() {
+            if entry_upper >= lower {
+                overlapping.push((entry_lower, entry_upper, sequence));
+            }
+        }
+
+        overlapping.extend(self.index.range(lower..=upper).map(|(&entry_lower, &(entry_upper, sequence))| (entry_lower, entry_upper, sequence)));
+
+        for (entry_lower, entry_upper, sequence) in overlapping {
+            let (_, value) = self.queue.remove(&(sequence, entry_lower)).unwrap();
+
+            self.index.remove(&entry_lower);
+
+            if entry_lower < lower {
+                self.insert(sequence, entry_lower, lower - T::one(), value);
+            }
+
+            if entry_upper > upper {
+                self.insert(sequence, upper + T::one(), entry_upper, value);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) -> Vec<Interval<T>> {
+        let intervals = self.iter().map(|(interval, _)| interval).collect();
+
+        self.queue.clear();
+
+        self.index.clear();
+
+        intervals
+    }
+
+    fn insert(&mut self, sequence: u64, lower: T, upper: T, value: V) {
+        self.queue.insert((sequence, lower), (upper, value));
+
+        self.index.insert(lower, (upper, sequence));
+    }
+}
+
+impl<T: IdType, V: Copy> Default for IntervalQueue<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    fn dump(queue: &IntervalQueue<u8, u32>) -> String {
+        let entries: Vec<String> = queue.iter().map(|(interval, value)| format!("{}:{}", interval, value)).collect();
+
+        entries.join(", ")
+    }
+
+    #[test]
+    fn test_push_and_pop() {
+        let mut queue = IntervalQueue::<u8, u32>::new();
+
+        assert_eq!(queue.is_empty(), true);
+        assert_eq!(queue.front(), None);
+
+        queue.push_back(10, 12, 1);
+        queue.push_back(0, 0, 2);
+        queue.push_back(5, 5, 3);
+
+        assert_eq!(dump(&queue), "[10,12]:1, [0]:2, [5]:3");
+
+        assert_eq!(queue.front(), Some((Interval::new(10, 12), 1)));
+        assert_eq!(queue.back(), Some((Interval::new(5, 5), 3)));
+
+        assert_eq!(queue.pop_front(), Some((Interval::new(10, 12), 1)));
+        assert_eq!(queue.pop_front(), Some((Interval::new(0, 0), 2)));
+
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_keeps_pieces_in_place() {
+        let mut queue = IntervalQueue::<u8, u32>::new();
+
+        queue.push_back(10, 20, 1);
+        queue.push_back(0, 5, 2);
+        queue.push_back(30, 40, 3);
+
+        queue.remove(15, 15);
+
+        assert_eq!(dump(&queue), "[10,14]:1, [16,20]:1, [0,5]:2, [30,40]:3");
+
+        queue.remove(4, 32);
+
+        assert_eq!(dump(&queue), "[0,3]:2, [33,40]:3");
+
+        queue.remove(0, 255);
+
+        assert_eq!(queue.is_empty(), true);
+    }
+
+    #[test]
+    fn test_remove_at_the_limits() {
+        let mut queue = IntervalQueue::<u8, u32>::new();
+
+        queue.push_back(0, 255, 1);
+
+        queue.remove(0, 0);
+        queue.remove(255, 255);
+
+        assert_eq!(dump(&queue), "[1,254]:1");
+
+        assert_eq!(queue.clear(), vec![Interval::new(1, 254)]);
+
+        assert_eq!(queue.is_empty(), true);
+    }
+}
@@ -1,3 +1,4 @@
+use std::cmp::{max, min, Reverse};
 use std::collections::Bound::{Excluded, Included, Unbounded};
 use std::collections::BTreeSet;
 use std::fmt;
@@ -6,22 +7,80 @@ use num::One;
 
 use crate::id_type::IdType;
 use crate::interval::Interval;
+use crate::random::Random;
+use crate::span_index::SpanIndex;
+use crate::state::StateError;
 
 pub struct Intervals<T: IdType> {
     intervals: BTreeSet<Interval<T>>,
+    // (upper - lower, lower, upper) of each interval, so that the largest can be found quickly
+    spans: BTreeSet<(u128, Reverse<T>, T)>,
+    // the sum of upper - lower for all intervals, this fits as there is a gap between intervals, but can
+    // wrap while an interval is being split
+    total_span: u128,
+    // the intervals ordered by lower value with the total span below each node, so that the nth value
+    // can be found without walking every interval
+    index: SpanIndex<T>,
 }
 
 impl<T: IdType> Intervals<T> {
     pub fn new() -> Self {
         Intervals::<T> {
             intervals: BTreeSet::new(),
+            spans: BTreeSet::new(),
+            total_span: 0,
+            index: SpanIndex::new(),
         }
     }
 
+    // rebuilds a set of intervals from a saved list, which must be exactly as iter() produced it
+
+    pub fn try_from_sorted(intervals: &[Interval<T>]) -> Result<Self, StateError> {
+        let mut result = Self::new();
+
+        let mut previous: Option<&Interval<T>> = None;
+
+        for interval in intervals {
+            if interval.upper() < interval.lower() {
+                return Err(StateError::InvalidInterval);
+            }
+
+            if let Some(previous) = previous {
+                if previous.upper() >= interval.lower() || interval.extends_lower(previous) {
+                    return Err(StateError::UnorderedIntervals);
+                }
+            }
+
+            result.insert_interval(interval.lower(), interval.upper());
+
+            previous = Some(interval);
+        }
+
+        Ok(result)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.intervals.is_empty()
     }
 
+    pub fn contains_value(&self, value: T) -> bool {
+        self.find(&Interval::new_single_value_interval(value)).is_some()
+    }
+
+    pub fn contains_any(&self, lower: T, upper: T) -> bool {
+        self.find(&Interval::new(lower, upper)).is_some()
+    }
+
+    pub fn contains_all(&self, lower: T, upper: T) -> bool {
+        match self.find(&Interval::new_single_value_interval(lower)) {
+            Some(interval) => interval.upper() >= upper,
+            None => false,
+        }
+    }
+
+    // the bulk free primitive, the whole interval is merged in with O(log n) work, or nothing is
+    // inserted and false is returned if any part of it is already present
+
     pub fn insert_interval(&mut self, lower: T, upper: T) -> bool {
         let interval = Interval::new(lower, upper);
 
@@ -34,6 +93,24 @@ impl<T: IdType> Intervals<T> {
         self.insert(interval)
     }
 
+    // None if every value of a u128 is present, as that count doesn't fit
+
+    pub fn count(&self) -> Option<u128> {
+        self.total_span.checked_add(self.intervals.len() as u128)
+    }
+
+    pub fn interval_count(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn largest_interval(&self) -> Option<Interval<T>> {
+        self.spans.iter().next_back().map(|(_, Reverse(lower), upper)| Interval::new(*lower, *upper))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Interval<T>> {
+        self.intervals.iter()
+    }
+
     pub fn dump(&self) -> String {
         format!("{}", self)
     }
@@ -44,7 +121,7 @@ impl<T: IdType> Intervals<T> {
         if let Some(first_interval) = first_it {
             let ret = first_interval.clone();
 
-            self.intervals.remove(&first_interval.clone());
+            self.remove_from_set(&first_interval.clone());
 
             return ret;
         }
@@ -59,8 +136,7 @@ impl<T: IdType> Intervals<T> {
 
         if first_interval.lower() != first_interval.upper()
         {
-            self.intervals
-                .insert(Interval::new(first_value + One::one(), first_interval.upper()));
+            self.insert_into_set(Interval::new(first_value + One::one(), first_interval.upper()));
         }
 
         first_value
@@ -69,16 +145,14 @@ impl<T: IdType> Intervals<T> {
     pub fn remove_value(&mut self, value: T) -> bool {
         if let Some(interval) = self.find(&Interval::new_single_value_interval(value)) {
             if interval.lower() < value {
-                self.intervals
-                    .insert(Interval::new(interval.lower(), value - One::one()));
+                self.insert_into_set(Interval::new(interval.lower(), value - One::one()));
             }
 
             if value < interval.upper() {
-                self.intervals
-                    .insert(Interval::new(value + One::one(), interval.upper()));
+                self.insert_into_set(Interval::new(value + One::one(), interval.upper()));
             }
 
-            self.intervals.remove(&interval);
+            self.remove_from_set(&interval);
 
             return true;
         }
@@ -97,10 +171,11 @@ impl<T: IdType> Intervals<T> {
             let intervals = self.intervals.range((Included(Interval::new(T::MIN, lower)), Unbounded));
 
             for interval in intervals {
-                if interval_to_remove.overlaps(interval) {
-                    remove_these.insert(interval.clone());
+                if interval.lower() > upper {
+                    break;
                 }
-                else if interval.contains_value(lower) || interval.contains_value(upper) {
+
+                if interval_to_remove.overlaps(interval) || interval.contains_value(lower) || interval.contains_value(upper) {
                     remove_these.insert(interval.clone());
                 }
 
@@ -115,12 +190,138 @@ impl<T: IdType> Intervals<T> {
         }
 
         for interval in remove_these {
-            self.intervals.remove(&interval);
+            self.remove_from_set(&interval);
         }
 
         for interval in add_these {
-            self.intervals.insert(interval);
+            self.insert_into_set(interval);
+        }
+    }
+
+    pub fn first_value_at_or_after(&self, value: T) -> Option<T> {
+        self.intervals_at_or_after(value).next()
+            .map(|interval| if interval.lower() < value { value } else { interval.lower() })
+    }
+
+    pub fn last_value_at_or_before(&self, value: T) -> Option<T> {
+        self.intervals_at_or_before(value).next()
+            .map(|interval| if interval.upper() > value { value } else { interval.upper() })
+    }
+
+    pub fn random_value(&self, random: &mut Random) -> Option<T> {
+        let offset = random.next_u128_at_most(self.index.last_offset()?);
+
+        let (lower, offset) = self.index.find(offset)?;
+
+        T::from_u128(offset).map(|offset| lower + offset)
+    }
+
+    fn span(interval: &Interval<T>) -> u128 {
+        (interval.upper() - interval.lower()).to_u128().unwrap()
+    }
+
+    pub fn find_block_at_or_after(&self, from: T, count: T) -> Option<T> {
+        self.find_aligned_block_at_or_after(from, count, T::one())
+    }
+
+    pub fn find_aligned_block_at_or_after(&self, from: T, count: T, alignment: T) -> Option<T> {
+        for interval in self.intervals_at_or_after(from) {
+            let mut lower = if interval.lower() < from { from } else { interval.lower() };
+
+            let remainder = lower % alignment;
+
+            if !remainder.is_zero() {
+                let padding = alignment - remainder;
+
+                if interval.upper() - lower < padding {
+                    continue;
+                }
+
+                lower = lower + padding;
+            }
+
+            if interval.upper() - lower >= count - T::one() {
+                return Some(lower);
+            }
+        }
+
+        None
+    }
+
+    pub fn find_aligned_block_at_or_before(&self, from: T, count: T, alignment: T) -> Option<T> {
+        for interval in self.intervals_at_or_before(from) {
+            let upper = if interval.upper() > from { from } else { interval.upper() };
+
+            if upper - interval.lower() < count - T::one() {
+                continue;
+            }
+
+            let mut lower = upper - (count - T::one());
+
+            lower = lower - lower % alignment;
+
+            if lower >= interval.lower() {
+                return Some(lower);
+            }
         }
+
+        None
+    }
+
+    pub fn intersection(&self, lower: T, upper: T) -> Vec<Interval<T>> {
+        self.intervals_at_or_after(lower)
+            .take_while(|interval| interval.lower() <= upper)
+            .map(|interval| Interval::new(max(interval.lower(), lower), min(interval.upper(), upper)))
+            .collect()
+    }
+
+    pub fn gaps(&self, lower: T, upper: T) -> Vec<Interval<T>> {
+        let mut gaps = Vec::new();
+
+        let mut next = Some(lower);
+
+        for interval in self.intervals_at_or_after(lower) {
+            let from = match next {
+                Some(from) => from,
+                None => break,
+            };
+
+            if interval.lower() > upper {
+                break;
+            }
+
+            if interval.lower() > from {
+                gaps.push(Interval::new(from, interval.lower() - T::one()));
+            }
+
+            next = if interval.upper() >= upper { None } else { Some(interval.upper() + T::one()) };
+        }
+
+        if let Some(from) = next {
+            gaps.push(Interval::new(from, upper));
+        }
+
+        gaps
+    }
+
+    fn intervals_at_or_before(&self, value: T) -> impl Iterator<Item = &Interval<T>> {
+        let key = Interval::new_single_value_interval(value);
+
+        let containing = self.intervals.range((Included(&key), Unbounded)).next()
+            .filter(|interval| interval.contains_value(value));
+
+        containing.into_iter().chain(self.intervals.range((Unbounded, Excluded(key))).rev())
+    }
+
+    fn intervals_at_or_after(&self, value: T) -> impl Iterator<Item = &Interval<T>> {
+        let key = Interval::new_single_value_interval(value);
+
+        // the interval containing the value can sort either side of the key
+
+        let containing = self.intervals.range((Unbounded, Excluded(&key))).next_back()
+            .filter(|interval| interval.contains_value(value));
+
+        containing.into_iter().chain(self.intervals.range((Included(key), Unbounded)))
     }
 
     fn find(&self, interval: &Interval<T>) -> Option<Interval<T>> {
@@ -185,12 +386,36 @@ impl<T: IdType> Intervals<T> {
         } else if prev_is {
             self.insert_or_merge_with_prev(interval, prev_it.unwrap().clone());
         } else {
-            self.intervals.insert(interval);
+            self.insert_into_set(interval);
         }
 
         true
     }
 
+    fn insert_into_set(&mut self, interval: Interval<T>) {
+        let span = Self::span(&interval);
+
+        self.spans.insert((span, Reverse(interval.lower()), interval.upper()));
+
+        self.total_span = self.total_span.wrapping_add(span);
+
+        self.index.insert(interval.lower(), interval.upper(), span);
+
+        self.intervals.insert(interval);
+    }
+
+    fn remove_from_set(&mut self, interval: &Interval<T>) {
+        if self.intervals.remove(interval) {
+            let span = Self::span(interval);
+
+            self.spans.remove(&(span, Reverse(interval.lower()), interval.upper()));
+
+            self.total_span = self.total_span.wrapping_sub(span);
+
+            self.index.remove(interval.lower(), interval.upper());
+        }
+    }
+
     fn insert_or_join_intervals(&mut self, interval: Interval<T>, next: Interval<T>, prev: Interval<T>) {
         let next_extends = next.extends_lower(&interval);
 
@@ -202,27 +427,27 @@ impl<T: IdType> Intervals<T> {
 
             let new_interval = Interval::new(prev.lower(), next.upper());
 
-            self.intervals.remove(&prev);
-            self.intervals.remove(&next);
-            self.intervals.insert(new_interval);
+            self.remove_from_set(&prev);
+            self.remove_from_set(&next);
+            self.insert_into_set(new_interval);
         } else if next_extends
         {
             // extends the next interval
 
             let new_interval = Interval::new(interval.lower(), next.upper());
 
-            self.intervals.remove(&next);
-            self.intervals.insert(new_interval);
+            self.remove_from_set(&next);
+            self.insert_into_set(new_interval);
         } else if prev_extends
         {
             // extends the previous interval
 
             let new_interval = Interval::new(prev.lower(), interval.upper());
 
-            self.intervals.remove(&prev);
-            self.intervals.insert(new_interval);
+            self.remove_from_set(&prev);
+            self.insert_into_set(new_interval);
         } else {
-            self.intervals.insert(interval);
+            self.insert_into_set(interval);
         }
     }
 
@@ -233,10 +458,10 @@ impl<T: IdType> Intervals<T> {
 
             let new_interval = Interval::new(interval.lower(), next.upper());
 
-            self.intervals.remove(&next);
-            self.intervals.insert(new_interval);
+            self.remove_from_set(&next);
+            self.insert_into_set(new_interval);
         } else {
-            self.intervals.insert(interval);
+            self.insert_into_set(interval);
         }
     }
 
@@ -247,14 +472,20 @@ impl<T: IdType> Intervals<T> {
 
             let new_interval = Interval::new(prev.lower(), interval.upper());
 
-            self.intervals.remove(&prev);
-            self.intervals.insert(new_interval);
+            self.remove_from_set(&prev);
+            self.insert_into_set(new_interval);
         } else {
-            self.intervals.insert(interval);
+            self.insert_into_set(interval);
         }
     }
 }
 
+impl<T: IdType> Default for Intervals<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: IdType> fmt::Display for Intervals<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut first = true;
@@ -275,6 +506,7 @@ impl<T: IdType> fmt::Display for Intervals<T> {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -312,6 +544,38 @@ mod tests {
         assert_eq!(intervals.is_empty(), true);
     }
 
+    #[test]
+    fn test_contains() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.contains_value(4), false);
+        assert_eq!(intervals.contains_any(0, 255), false);
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+
+        assert_eq!(intervals.contains_value(3), false);
+        assert_eq!(intervals.contains_value(4), true);
+        assert_eq!(intervals.contains_value(15), true);
+        assert_eq!(intervals.contains_value(21), false);
+
+        assert_eq!(intervals.contains_any(6, 9), false);
+        assert_eq!(intervals.contains_any(0, 3), false);
+        assert_eq!(intervals.contains_any(21, 255), false);
+        assert_eq!(intervals.contains_any(6, 10), true);
+        assert_eq!(intervals.contains_any(0, 255), true);
+        assert_eq!(intervals.contains_any(12, 13), true);
+        assert_eq!(intervals.contains_any(20, 30), true);
+
+        assert_eq!(intervals.contains_all(4, 5), true);
+        assert_eq!(intervals.contains_all(4, 4), true);
+        assert_eq!(intervals.contains_all(11, 19), true);
+        assert_eq!(intervals.contains_all(10, 20), true);
+        assert_eq!(intervals.contains_all(3, 5), false);
+        assert_eq!(intervals.contains_all(4, 10), false);
+        assert_eq!(intervals.contains_all(15, 21), false);
+    }
+
     #[test]
     fn test_insert_value() {
         let mut intervals = Intervals::<u8>::new();
@@ -802,6 +1066,322 @@ mod tests {
         assert_eq!(intervals.dump(), "");
     }
 
+    #[test]
+    fn test_first_value_at_or_after() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.first_value_at_or_after(0), None);
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_value(8), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+
+        assert_eq!(intervals.first_value_at_or_after(0), Some(4));
+        assert_eq!(intervals.first_value_at_or_after(4), Some(4));
+        assert_eq!(intervals.first_value_at_or_after(5), Some(5));
+        assert_eq!(intervals.first_value_at_or_after(6), Some(8));
+        assert_eq!(intervals.first_value_at_or_after(8), Some(8));
+        assert_eq!(intervals.first_value_at_or_after(9), Some(10));
+        assert_eq!(intervals.first_value_at_or_after(15), Some(15));
+        assert_eq!(intervals.first_value_at_or_after(20), Some(20));
+        assert_eq!(intervals.first_value_at_or_after(21), None);
+        assert_eq!(intervals.first_value_at_or_after(255), None);
+    }
+
+    #[test]
+    fn test_last_value_at_or_before() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.last_value_at_or_before(255), None);
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_value(8), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+
+        assert_eq!(intervals.last_value_at_or_before(0), None);
+        assert_eq!(intervals.last_value_at_or_before(3), None);
+        assert_eq!(intervals.last_value_at_or_before(4), Some(4));
+        assert_eq!(intervals.last_value_at_or_before(5), Some(5));
+        assert_eq!(intervals.last_value_at_or_before(7), Some(5));
+        assert_eq!(intervals.last_value_at_or_before(8), Some(8));
+        assert_eq!(intervals.last_value_at_or_before(9), Some(8));
+        assert_eq!(intervals.last_value_at_or_before(10), Some(10));
+        assert_eq!(intervals.last_value_at_or_before(15), Some(15));
+        assert_eq!(intervals.last_value_at_or_before(20), Some(20));
+        assert_eq!(intervals.last_value_at_or_before(255), Some(20));
+    }
+
+    #[test]
+    fn test_random_value() {
+        let mut intervals = Intervals::<u8>::new();
+
+        let mut random = Random::new(Some(1));
+
+        assert_eq!(intervals.random_value(&mut random), None);
+
+        assert_eq!(intervals.insert_value(4), true);
+        assert_eq!(intervals.insert_interval(10, 11), true);
+        assert_eq!(intervals.insert_value(255), true);
+
+        let mut seen = Intervals::<u8>::new();
+
+        for _ in 0..100 {
+            let value = intervals.random_value(&mut random).unwrap();
+
+            assert_eq!(value == 4 || value == 10 || value == 11 || value == 255, true);
+
+            seen.insert_value(value);
+        }
+
+        assert_eq!(seen.dump(), "[4], [10,11], [255]");
+    }
+
+    #[test]
+    fn test_random_value_full_range() {
+        let mut intervals = Intervals::<u128>::new();
+
+        let mut random = Random::new(Some(1));
+
+        assert_eq!(intervals.insert_interval(u128::MIN, u128::MAX), true);
+
+        assert_eq!(intervals.random_value(&mut random).is_some(), true);
+
+        assert_eq!(intervals.remove_value(1), true);
+
+        for _ in 0..100 {
+            assert_ne!(intervals.random_value(&mut random), Some(1));
+        }
+    }
+
+    #[test]
+    fn test_find_block_at_or_after() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.find_block_at_or_after(0, 1), None);
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+        assert_eq!(intervals.insert_interval(30, 35), true);
+
+        assert_eq!(intervals.find_block_at_or_after(0, 1), Some(4));
+        assert_eq!(intervals.find_block_at_or_after(0, 2), Some(4));
+        assert_eq!(intervals.find_block_at_or_after(0, 3), Some(10));
+        assert_eq!(intervals.find_block_at_or_after(0, 11), Some(10));
+        assert_eq!(intervals.find_block_at_or_after(0, 12), None);
+
+        assert_eq!(intervals.find_block_at_or_after(5, 1), Some(5));
+        assert_eq!(intervals.find_block_at_or_after(5, 2), Some(10));
+        assert_eq!(intervals.find_block_at_or_after(15, 6), Some(15));
+        assert_eq!(intervals.find_block_at_or_after(16, 6), Some(30));
+        assert_eq!(intervals.find_block_at_or_after(15, 7), None);
+        assert_eq!(intervals.find_block_at_or_after(36, 1), None);
+    }
+
+    #[test]
+    fn test_find_block_at_or_after_full_range() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.insert_interval(u8::MIN, u8::MAX), true);
+
+        assert_eq!(intervals.find_block_at_or_after(0, 255), Some(0));
+        assert_eq!(intervals.find_block_at_or_after(1, 255), Some(1));
+        assert_eq!(intervals.find_block_at_or_after(2, 255), None);
+        assert_eq!(intervals.find_block_at_or_after(255, 1), Some(255));
+    }
+
+    #[test]
+    fn test_find_aligned_block_at_or_after() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.insert_interval(3, 9), true);
+        assert_eq!(intervals.insert_interval(14, 40), true);
+        assert_eq!(intervals.insert_interval(60, 255), true);
+
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 4, 4), Some(4));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 5, 4), Some(4));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 7, 4), Some(16));
+        assert_eq!(intervals.find_aligned_block_at_or_after(5, 4, 4), Some(16));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 16, 16), Some(16));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 26, 16), Some(64));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 64, 64), Some(64));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 128, 64), Some(64));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 128, 128), Some(128));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 129, 128), None);
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 1, 255), Some(255));
+        assert_eq!(intervals.find_aligned_block_at_or_after(0, 2, 255), None);
+    }
+
+    #[test]
+    fn test_find_aligned_block_at_or_before() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.find_aligned_block_at_or_before(255, 1, 1), None);
+
+        assert_eq!(intervals.insert_interval(3, 9), true);
+        assert_eq!(intervals.insert_interval(14, 40), true);
+        assert_eq!(intervals.insert_interval(60, 250), true);
+
+        assert_eq!(intervals.find_aligned_block_at_or_before(255, 1, 1), Some(250));
+        assert_eq!(intervals.find_aligned_block_at_or_before(255, 4, 1), Some(247));
+        assert_eq!(intervals.find_aligned_block_at_or_before(255, 4, 4), Some(244));
+        assert_eq!(intervals.find_aligned_block_at_or_before(255, 64, 64), Some(128));
+        assert_eq!(intervals.find_aligned_block_at_or_before(100, 64, 64), None);
+        assert_eq!(intervals.find_aligned_block_at_or_before(100, 10, 1), Some(91));
+        assert_eq!(intervals.find_aligned_block_at_or_before(59, 10, 1), Some(31));
+        assert_eq!(intervals.find_aligned_block_at_or_before(59, 10, 16), Some(16));
+        assert_eq!(intervals.find_aligned_block_at_or_before(59, 7, 1), Some(34));
+        assert_eq!(intervals.find_aligned_block_at_or_before(13, 7, 1), Some(3));
+        assert_eq!(intervals.find_aligned_block_at_or_before(13, 7, 2), None);
+        assert_eq!(intervals.find_aligned_block_at_or_before(8, 6, 1), Some(3));
+        assert_eq!(intervals.find_aligned_block_at_or_before(2, 1, 1), None);
+    }
+
+    #[test]
+    fn test_insert_interval_spanning_multiple_intervals() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.insert_interval(2, 3), true);
+        assert_eq!(intervals.insert_interval(6, 7), true);
+        assert_eq!(intervals.insert_interval(10, 11), true);
+
+        assert_eq!(intervals.insert_interval(1, 12), false);
+        assert_eq!(intervals.insert_interval(2, 12), false);
+        assert_eq!(intervals.insert_interval(4, 10), false);
+        assert_eq!(intervals.insert_interval(0, 6), false);
+
+        assert_eq!(intervals.dump(), "[2,3], [6,7], [10,11]");
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_interval(8, 9), true);
+
+        assert_eq!(intervals.dump(), "[2,11]");
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.iter().next(), None);
+
+        assert_eq!(intervals.insert_interval(30, 35), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+        assert_eq!(intervals.insert_value(40), true);
+
+        let all: Vec<&Interval<u8>> = intervals.iter().collect();
+
+        assert_eq!(all, vec![&Interval::new(10, 20), &Interval::new(30, 35), &Interval::new(40, 40)]);
+    }
+
+    #[test]
+    fn test_count() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.count(), Some(0));
+        assert_eq!(intervals.interval_count(), 0);
+        assert_eq!(intervals.largest_interval(), None);
+
+        assert_eq!(intervals.insert_interval(10, 20), true);
+        assert_eq!(intervals.insert_interval(30, 35), true);
+
+        assert_eq!(intervals.count(), Some(17));
+        assert_eq!(intervals.interval_count(), 2);
+        assert_eq!(intervals.largest_interval(), Some(Interval::new(10, 20)));
+
+        assert_eq!(intervals.remove_value(15), true);
+
+        assert_eq!(intervals.count(), Some(16));
+        assert_eq!(intervals.interval_count(), 3);
+        assert_eq!(intervals.largest_interval(), Some(Interval::new(30, 35)));
+
+        intervals.remove_interval(12, 32);
+
+        assert_eq!(intervals.count(), Some(5));
+        assert_eq!(intervals.largest_interval(), Some(Interval::new(33, 35)));
+
+        assert_eq!(intervals.insert_interval(12, 32), true);
+
+        assert_eq!(intervals.count(), Some(26));
+        assert_eq!(intervals.interval_count(), 1);
+
+        assert_eq!(intervals.remove_first_value(), 10);
+
+        assert_eq!(intervals.count(), Some(25));
+    }
+
+    #[test]
+    fn test_largest_interval_prefers_lowest() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.insert_interval(30, 35), true);
+        assert_eq!(intervals.insert_interval(10, 15), true);
+
+        assert_eq!(intervals.largest_interval(), Some(Interval::new(10, 15)));
+    }
+
+    #[test]
+    fn test_count_full_range() {
+        let mut intervals = Intervals::<u128>::new();
+
+        assert_eq!(intervals.insert_interval(u128::MIN, u128::MAX), true);
+
+        assert_eq!(intervals.count(), None);
+
+        assert_eq!(intervals.remove_value(100), true);
+
+        assert_eq!(intervals.count(), Some(u128::MAX));
+        assert_eq!(intervals.interval_count(), 2);
+        assert_eq!(intervals.largest_interval(), Some(Interval::new(101, u128::MAX)));
+    }
+
+    #[test]
+    fn test_intersection() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.intersection(0, 255), vec![]);
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+
+        assert_eq!(intervals.intersection(0, 255), vec![Interval::new(4, 5), Interval::new(10, 20)]);
+        assert_eq!(intervals.intersection(5, 12), vec![Interval::new(5, 5), Interval::new(10, 12)]);
+        assert_eq!(intervals.intersection(12, 15), vec![Interval::new(12, 15)]);
+        assert_eq!(intervals.intersection(6, 9), vec![]);
+    }
+
+    #[test]
+    fn test_try_from_sorted() {
+        let intervals = Intervals::<u8>::try_from_sorted(&[Interval::new(0, 5), Interval::new(7, 7), Interval::new(10, 255)]).unwrap();
+
+        assert_eq!(intervals.dump(), "[0,5], [7], [10,255]");
+
+        assert_eq!(intervals.count(), Some(253));
+
+        assert!(matches!(Intervals::<u8>::try_from_sorted(&[Interval::new(10, 20), Interval::new(0, 5)]), Err(StateError::UnorderedIntervals)));
+        assert!(matches!(Intervals::<u8>::try_from_sorted(&[Interval::new(0, 5), Interval::new(5, 20)]), Err(StateError::UnorderedIntervals)));
+        assert!(matches!(Intervals::<u8>::try_from_sorted(&[Interval::new(0, 5), Interval::new(6, 20)]), Err(StateError::UnorderedIntervals)));
+    }
+
+    #[test]
+    fn test_gaps() {
+        let mut intervals = Intervals::<u8>::new();
+
+        assert_eq!(intervals.gaps(0, 255), vec![Interval::new(0, 255)]);
+
+        assert_eq!(intervals.insert_interval(4, 5), true);
+        assert_eq!(intervals.insert_interval(10, 20), true);
+
+        assert_eq!(intervals.gaps(0, 255), vec![Interval::new(0, 3), Interval::new(6, 9), Interval::new(21, 255)]);
+        assert_eq!(intervals.gaps(5, 12), vec![Interval::new(6, 9)]);
+        assert_eq!(intervals.gaps(12, 15), vec![]);
+        assert_eq!(intervals.gaps(15, 25), vec![Interval::new(21, 25)]);
+        assert_eq!(intervals.gaps(6, 6), vec![Interval::new(6, 6)]);
+
+        assert_eq!(intervals.insert_interval(0, 3), true);
+        assert_eq!(intervals.insert_interval(21, 255), true);
+
+        assert_eq!(intervals.gaps(0, 255), vec![Interval::new(6, 9)]);
+    }
+
     #[test]
     fn test_remove_interval()
     {
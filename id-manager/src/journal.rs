@@ -0,0 +1,494 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::id_error::IdError;
+use crate::id_type::IdType;
+use crate::snapshot::{self, Body, SnapshotError};
+use crate::state::{IdManagerState, StateError};
+
+// a journal file is a snapshot followed by the records of every change made since it was taken.
+// compacting writes a fresh snapshot to a temporary file and renames it over the journal, so the
+// file on disk is always either the old snapshot and records or the new snapshot.
+//
+// each record is a kind byte, the lower id and the distance to the upper id as varints, a cursor
+// for allocations, then a CRC-32 of the record. a crash can leave a partial record at the end,
+// which recovery truncates away, but a damaged record with more after it fails recovery rather
+// than silently dropping everything that follows it.
+//
+// every record is synced to disk before append() returns, and the directory is synced after each
+// rename, so a change that has been journalled survives a crash or power loss.
+
+const ALLOCATED: u8 = 1;
+
+const MARKED_USED: u8 = 2;
+
+const FREED: u8 = 3;
+
+// a crash part way through an append leaves the last record incomplete, anything else that can't
+// be read is damage
+
+#[derive(PartialEq, Eq, Debug)]
+enum DecodeError {
+    Incomplete,
+    Damaged,
+}
+
+impl From<SnapshotError> for DecodeError {
+    fn from(error: SnapshotError) -> Self {
+        match error {
+            SnapshotError::Truncated => DecodeError::Incomplete,
+            _ => DecodeError::Damaged,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum JournalRecord<T: IdType> {
+    Allocated { lower: T, upper: T, cursor: Option<T> },
+    MarkedUsed { lower: T, upper: T },
+    Freed { lower: T, upper: T },
+}
+
+impl<T: IdType> JournalRecord<T> {
+    fn encode(&self) -> Vec<u8> {
+        let mut record = Vec::new();
+
+        let (kind, lower, upper) = match *self {
+            JournalRecord::Allocated { lower, upper, .. } => (ALLOCATED, lower, upper),
+            JournalRecord::MarkedUsed { lower, upper } => (MARKED_USED, lower, upper),
+            JournalRecord::Freed { lower, upper } => (FREED, lower, upper),
+        };
+
+        record.push(kind);
+
+        snapshot::write_varint(&mut record, snapshot::to_u128(lower));
+        snapshot::write_varint(&mut record, snapshot::to_u128(upper) - snapshot::to_u128(lower));
+
+        if let JournalRecord::Allocated { cursor, .. } = self {
+            match cursor {
+                Some(cursor) => {
+                    record.push(1);
+
+                    snapshot::write_varint(&mut record, snapshot::to_u128(*cursor));
+                }
+                None => record.push(0),
+            }
+        }
+
+        let checksum = snapshot::crc32(&record);
+
+        record.extend_from_slice(&checksum.to_le_bytes());
+
+        record
+    }
+
+    fn decode(body: &mut Body) -> Result<Self, DecodeError> {
+        let start = body.position();
+
+        let kind = body.read_byte()?;
+
+        let lower = body.read_varint()?;
+        let upper = lower.checked_add(body.read_varint()?).ok_or(DecodeError::Damaged)?;
+
+        let (lower, upper) = (snapshot::from_u128(lower)?, snapshot::from_u128(upper)?);
+
+        let record = match kind {
+            ALLOCATED => {
+                let cursor = match body.read_byte()? {
+                    0 => None,
+                    _ => Some(body.read_value()?),
+                };
+
+                JournalRecord::Allocated { lower, upper, cursor }
+            }
+            MARKED_USED => JournalRecord::MarkedUsed { lower, upper },
+            FREED => JournalRecord::Freed { lower, upper },
+            _ => return Err(DecodeError::Damaged),
+        };
+
+        let end = body.position();
+
+        let checksum = body.read_bytes(4)?;
+
+        if u32::from_le_bytes(checksum.try_into().unwrap()) != snapshot::crc32(&body.data()[start..end]) {
+            return Err(DecodeError::Damaged);
+        }
+
+        Ok(record)
+    }
+}
+
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    Snapshot(SnapshotError),
+    Replay(IdError),
+    // the offset in the file of a damaged record that isn't the last one
+    Damaged(u64),
+    NotEnabled,
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io(error) => write!(f, "journal i/o error: {}", error),
+            JournalError::Snapshot(error) => write!(f, "journal snapshot error: {}", error),
+            JournalError::Replay(error) => write!(f, "journal replay failed: {}", error),
+            JournalError::Damaged(offset) => write!(f, "journal record at offset {} is damaged", offset),
+            JournalError::NotEnabled => write!(f, "journal is not enabled"),
+        }
+    }
+}
+
+impl Error for JournalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JournalError::Io(error) => Some(error),
+            JournalError::Snapshot(error) => Some(error),
+            JournalError::Replay(error) => Some(error),
+            JournalError::Damaged(_) | JournalError::NotEnabled => None,
+        }
+    }
+}
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> Self {
+        JournalError::Io(error)
+    }
+}
+
+impl From<SnapshotError> for JournalError {
+    fn from(error: SnapshotError) -> Self {
+        JournalError::Snapshot(error)
+    }
+}
+
+impl From<StateError> for JournalError {
+    fn from(error: StateError) -> Self {
+        JournalError::Snapshot(SnapshotError::InvalidState(error))
+    }
+}
+
+// the journal, opened for appending, with the snapshot and the records to replay on top of it
+
+pub type Recovered<T> = (Journal, IdManagerState<T>, Vec<JournalRecord<T>>);
+
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    // once a record fails to be written the journal no longer matches the manager, nothing more
+    // is written until compact() replaces it with a complete snapshot
+    failed: bool,
+}
+
+impl Journal {
+    pub fn create<T: IdType>(path: impl AsRef<Path>, state: &IdManagerState<T>) -> Result<Self, JournalError> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut temp_path = path.clone().into_os_string();
+
+        temp_path.push(".tmp");
+
+        {
+            let mut temp = File::create(&temp_path)?;
+
+            snapshot::write_snapshot(state, &mut temp)?;
+
+            temp.sync_all()?;
+        }
+
+        fs::rename(&temp_path, &path)?;
+
+        Self::sync_directory(&path)?;
+
+        Self::open(path, None)
+    }
+
+    // the rename isn't durable until the directory entry is synced too, std can't open a directory
+    // to sync it on other platforms so they rely on the file system to make the rename durable
+
+    #[cfg(unix)]
+    fn sync_directory(path: &Path) -> Result<(), JournalError> {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        File::open(directory)?.sync_all()?;
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn sync_directory(_path: &Path) -> Result<(), JournalError> {
+        Ok(())
+    }
+
+    // opens an existing journal for appending, after dropping anything past length
+
+    fn open(path: PathBuf, length: Option<u64>) -> Result<Self, JournalError> {
+        let file = OpenOptions::new().append(true).open(&path)?;
+
+        if let Some(length) = length {
+            file.set_len(length)?;
+        }
+
+        Ok(Journal { path, file, failed: false })
+    }
+
+    pub fn recover<T: IdType>(path: impl AsRef<Path>) -> Result<Recovered<T>, JournalError> {
+        let path = path.as_ref().to_path_buf();
+
+        let data = fs::read(&path)?;
+
+        let mut reader = &data[..];
+
+        let state = snapshot::read_snapshot(&mut reader)?;
+
+        let snapshot_length = data.len() - reader.len();
+
+        let mut body = Body::new(reader);
+
+        let mut records = Vec::new();
+
+        let mut valid_length = 0;
+
+        while valid_length < reader.len() {
+            match JournalRecord::decode(&mut body) {
+                Ok(record) => records.push(record),
+                Err(DecodeError::Incomplete) => break,
+                // a torn write can also leave the last record whole but wrong
+                Err(DecodeError::Damaged) if body.position() == reader.len() => break,
+                Err(DecodeError::Damaged) => return Err(JournalError::Damaged((snapshot_length + valid_length) as u64)),
+            }
+
+            valid_length = body.position();
+        }
+
+        let journal = Self::open(path, Some((snapshot_length + valid_length) as u64))?;
+
+        Ok((journal, state, records))
+    }
+
+    pub fn append<T: IdType>(&mut self, record: JournalRecord<T>) {
+        if self.failed {
+            return;
+        }
+
+        if self.file.write_all(&record.encode()).is_err() || self.file.sync_data().is_err() {
+            self.failed = true;
+        }
+    }
+
+    pub fn mark_failed(&mut self) {
+        self.failed = true;
+    }
+
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn compact<T: IdType>(&mut self, state: &IdManagerState<T>) -> Result<(), JournalError> {
+        *self = Self::create(&self.path, state)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::interval::Interval;
+    use crate::reuse_policy::ReusePolicy;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("id-manager-{}-{}.journal", name, std::process::id()))
+    }
+
+    fn state() -> IdManagerState<u16> {
+        IdManagerState {
+            min_id: 0,
+            max_id: 999,
+            policy: ReusePolicy::ReuseSlow,
+            next_to_allocate: Some(0),
+            ranges: vec![Interval::new(0, 999)],
+            free_ids: vec![Interval::new(0, 999)],
+            reservations: vec![],
+        }
+    }
+
+    fn records() -> Vec<JournalRecord<u16>> {
+        vec![
+            JournalRecord::Allocated { lower: 0, upper: 9, cursor: Some(10) },
+            JournalRecord::MarkedUsed { lower: 500, upper: 500 },
+            JournalRecord::Freed { lower: 5, upper: 9 },
+            JournalRecord::Allocated { lower: 999, upper: 999, cursor: None },
+        ]
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        for record in records() {
+            let encoded = record.encode();
+
+            assert_eq!(JournalRecord::<u16>::decode(&mut Body::new(&encoded)).unwrap(), record);
+
+            let mut corrupted = encoded.clone();
+
+            corrupted[1] ^= 0x01;
+
+            assert_eq!(JournalRecord::<u16>::decode(&mut Body::new(&corrupted)), Err(DecodeError::Damaged));
+
+            assert_eq!(JournalRecord::<u16>::decode(&mut Body::new(&encoded[..encoded.len() - 1])), Err(DecodeError::Incomplete));
+        }
+    }
+
+    #[test]
+    fn test_create_append_and_recover() {
+        let path = temp_path("create_append_and_recover");
+
+        {
+            let mut journal = Journal::create(&path, &state()).unwrap();
+
+            for record in records() {
+                journal.append(record);
+            }
+
+            assert_eq!(journal.has_failed(), false);
+        }
+
+        let (_journal, recovered, recovered_records) = Journal::recover::<u16>(&path).unwrap();
+
+        assert_eq!(recovered, state());
+
+        assert_eq!(recovered_records, records());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_drops_partial_record() {
+        let path = temp_path("recover_drops_partial_record");
+
+        {
+            let mut journal = Journal::create(&path, &state()).unwrap();
+
+            for record in records() {
+                journal.append(record);
+            }
+        }
+
+        let length = fs::metadata(&path).unwrap().len();
+
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 2).unwrap();
+
+        {
+            let (mut journal, _, recovered_records) = Journal::recover::<u16>(&path).unwrap();
+
+            assert_eq!(recovered_records, records()[..3].to_vec());
+
+            journal.append(JournalRecord::<u16>::Freed { lower: 0, upper: 4 });
+        }
+
+        let (_journal, _, recovered_records) = Journal::recover::<u16>(&path).unwrap();
+
+        assert_eq!(recovered_records.len(), 4);
+
+        assert_eq!(recovered_records[3], JournalRecord::Freed { lower: 0, upper: 4 });
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_drops_damaged_last_record() {
+        let path = temp_path("recover_drops_damaged_last_record");
+
+        {
+            let mut journal = Journal::create(&path, &state()).unwrap();
+
+            for record in records() {
+                journal.append(record);
+            }
+        }
+
+        let mut data = fs::read(&path).unwrap();
+
+        let length = data.len();
+
+        data[length - 1] ^= 0x01;
+
+        fs::write(&path, &data).unwrap();
+
+        let (_journal, _, recovered_records) = Journal::recover::<u16>(&path).unwrap();
+
+        assert_eq!(recovered_records, records()[..3].to_vec());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_fails_on_damaged_record_before_the_end() {
+        let path = temp_path("recover_fails_on_damaged_record_before_the_end");
+
+        {
+            let mut journal = Journal::create(&path, &state()).unwrap();
+
+            for record in records() {
+                journal.append(record);
+            }
+        }
+
+        let records_length: usize = records().iter().map(|record| record.encode().len()).sum();
+
+        let mut data = fs::read(&path).unwrap();
+
+        let first_record = data.len() - records_length;
+
+        data[first_record + 1] ^= 0x01;
+
+        fs::write(&path, &data).unwrap();
+
+        assert!(matches!(Journal::recover::<u16>(&path), Err(JournalError::Damaged(offset)) if offset == first_record as u64));
+
+        // nothing is truncated, so the records are still there to be salvaged
+
+        assert_eq!(fs::read(&path).unwrap(), data);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compact() {
+        let path = temp_path("compact");
+
+        let mut journal = Journal::create(&path, &state()).unwrap();
+
+        for record in records() {
+            journal.append(record);
+        }
+
+        let mut compacted = state();
+
+        compacted.free_ids = vec![Interval::new(10, 499)];
+
+        journal.compact(&compacted).unwrap();
+
+        let (_journal, recovered, recovered_records) = Journal::recover::<u16>(&path).unwrap();
+
+        assert_eq!(recovered, compacted);
+
+        assert!(recovered_records.is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_missing_file() {
+        assert!(matches!(Journal::recover::<u16>(temp_path("missing")), Err(JournalError::Io(_))));
+    }
+}
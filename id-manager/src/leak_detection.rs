@@ -0,0 +1,149 @@
+use std::backtrace::Backtrace;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::Location;
+
+use crate::id_type::IdType;
+
+#[derive(Clone, Debug)]
+pub struct AllocationSite {
+    location: &'static Location<'static>,
+    backtrace: Option<String>,
+}
+
+impl AllocationSite {
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    pub fn backtrace(&self) -> Option<&str> {
+        self.backtrace.as_deref()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TrackedAllocation<T: IdType> {
+    lower: T,
+    upper: T,
+    site: AllocationSite,
+}
+
+impl<T: IdType> TrackedAllocation<T> {
+    pub fn lower(&self) -> T {
+        self.lower
+    }
+
+    pub fn upper(&self) -> T {
+        self.upper
+    }
+
+    pub fn site(&self) -> &AllocationSite {
+        &self.site
+    }
+}
+
+impl<T: IdType> fmt::Display for TrackedAllocation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lower == self.upper {
+            write!(f, "[{}] allocated at {}", self.lower, self.site.location)
+        } else {
+            write!(f, "[{},{}] allocated at {}", self.lower, self.upper, self.site.location)
+        }
+    }
+}
+
+// allocations are keyed by their lowest id, they never overlap as they are all currently allocated
+
+pub struct LeakDetection<T: IdType> {
+    capture_backtraces: bool,
+    outstanding: BTreeMap<T, TrackedAllocation<T>>,
+    released: BTreeMap<T, TrackedAllocation<T>>,
+}
+
+impl<T: IdType> LeakDetection<T> {
+    pub fn new(capture_backtraces: bool) -> Self {
+        LeakDetection { capture_backtraces, outstanding: BTreeMap::new(), released: BTreeMap::new() }
+    }
+
+    pub fn allocated(&mut self, lower: T, upper: T, location: &'static Location<'static>) {
+        let backtrace = if self.capture_backtraces { Some(Backtrace::force_capture().to_string()) } else { None };
+
+        self.outstanding.insert(lower, TrackedAllocation { lower, upper, site: AllocationSite { location, backtrace } });
+    }
+
+    pub fn released(&mut self, lower: T) {
+        if let Some(allocation) = self.outstanding.remove(&lower) {
+            self.released.insert(lower, allocation);
+        }
+    }
+
+    pub fn freed(&mut self, lower: T, upper: T) {
+        Self::remove_within(&mut self.outstanding, lower, upper);
+
+        Self::remove_within(&mut self.released, lower, upper);
+    }
+
+    pub fn outstanding(&self) -> Vec<TrackedAllocation<T>> {
+        self.outstanding.values().cloned().collect()
+    }
+
+    pub fn released_allocations(&self) -> Vec<TrackedAllocation<T>> {
+        self.released.values().cloned().collect()
+    }
+
+    fn remove_within(allocations: &mut BTreeMap<T, TrackedAllocation<T>>, lower: T, upper: T) {
+        let keys: Vec<T> = allocations.range(lower..=upper).map(|(key, _)| *key).collect();
+
+        for key in keys {
+            allocations.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocated_and_freed() {
+        let mut leak_detection = LeakDetection::<u8>::new(false);
+
+        let location = Location::caller();
+
+        leak_detection.allocated(1, 1, location);
+        leak_detection.allocated(5, 10, location);
+
+        let outstanding = leak_detection.outstanding();
+
+        assert_eq!(outstanding.len(), 2);
+
+        assert_eq!(format!("{}", outstanding[0]), format!("[1] allocated at {}", location));
+        assert_eq!(format!("{}", outstanding[1]), format!("[5,10] allocated at {}", location));
+
+        assert_eq!(outstanding[1].site().backtrace(), None);
+
+        leak_detection.freed(5, 10);
+
+        assert_eq!(leak_detection.outstanding().len(), 1);
+    }
+
+    #[test]
+    fn test_released() {
+        let mut leak_detection = LeakDetection::<u8>::new(true);
+
+        leak_detection.allocated(1, 1, Location::caller());
+
+        assert_eq!(leak_detection.outstanding()[0].site().backtrace().is_some(), true);
+
+        leak_detection.released(1);
+
+        assert_eq!(leak_detection.outstanding().len(), 0);
+
+        assert_eq!(leak_detection.released_allocations().len(), 1);
+
+        leak_detection.freed(0, 10);
+
+        assert_eq!(leak_detection.released_allocations().len(), 0);
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+use std::time::Instant;
+
+use crate::id_type::IdType;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Lease<T: IdType> {
+    id: T,
+    expiry: Instant,
+}
+
+impl<T: IdType> Lease<T> {
+    pub fn new(id: T, expiry: Instant) -> Self {
+        Lease { id, expiry }
+    }
+
+    pub fn id(&self) -> T {
+        self.id
+    }
+
+    pub fn expiry(&self) -> Instant {
+        self.expiry
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expiry <= now
+    }
+}
+
+impl<T: IdType> fmt::Display for Lease<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.id)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let now = Instant::now();
+
+        let lease = Lease::<u8>::new(10, now + Duration::from_secs(5));
+
+        assert_eq!(lease.id(), 10);
+
+        assert_eq!(lease.expiry(), now + Duration::from_secs(5));
+
+        assert_eq!(lease.is_expired(now), false);
+
+        assert_eq!(lease.is_expired(now + Duration::from_secs(5)), true);
+
+        assert_eq!(format!("{}", lease), "[10]");
+    }
+}
@@ -2,13 +2,50 @@
 
 mod interval;
 mod intervals;
+mod interval_queue;
 mod id_manager;
 mod smart_id;
+mod smart_id_block;
 mod thread_safe_id_manager;
 mod id_type;
 mod reuse_policy;
+mod id_error;
+mod direction;
+mod random;
+mod span_index;
+mod clock;
+mod quarantine;
+mod allocation_strategy;
+mod generational_id;
+mod generations;
+mod reservation;
+mod values;
+mod observer;
+mod leak_detection;
+mod lease;
+mod state;
+mod snapshot;
+mod journal;
+#[cfg(feature = "serde")]
+mod serde_support;
 
 pub use thread_safe_id_manager::ThreadSafeIdManager as IdManager;
 pub use smart_id::SmartId as Id;
+pub use smart_id_block::SmartIdBlock as IdBlock;
 pub use id_type::IdType;
 pub use reuse_policy::ReusePolicy;
+pub use id_error::{IdError, ShrinkRangeError};
+pub use direction::Direction;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use quarantine::QuarantinePeriod;
+pub use allocation_strategy::{AllocationStrategy, IntoAllocationStrategy, FastStrategy, SlowStrategy, FastHighestStrategy, SlowDescendingStrategy, RandomStrategy, OldestFreedStrategy};
+pub use generational_id::GenerationalId;
+pub use reservation::Reservation;
+pub use observer::IdManagerObserver;
+pub use leak_detection::{AllocationSite, TrackedAllocation};
+pub use lease::Lease;
+pub use state::{IdManagerState, StateError};
+pub use snapshot::SnapshotError;
+pub use journal::JournalError;
+pub use interval::Interval;
+pub use intervals::Intervals;
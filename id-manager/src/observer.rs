@@ -0,0 +1,17 @@
+use crate::id_type::IdType;
+
+// observers are called while the manager is locked, so they must not call back into the manager
+
+pub trait IdManagerObserver<T: IdType>: Send + Sync {
+    fn on_allocate(&self, _lower: T, _upper: T) {}
+
+    fn on_free(&self, _lower: T, _upper: T) {}
+
+    fn on_mark_used(&self, _lower: T, _upper: T) {}
+
+    fn on_exhausted(&self) {}
+
+    // called once each time the free count drops to or below the watermark
+
+    fn on_low_watermark(&self, _free_count: u128) {}
+}
@@ -0,0 +1,197 @@
+use std::time::{Duration, Instant};
+
+use crate::id_type::IdType;
+use crate::interval::Interval;
+use crate::interval_queue::IntervalQueue;
+use crate::intervals::Intervals;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum QuarantinePeriod {
+    Allocations(u64),
+    Duration(Duration),
+}
+
+#[derive(Clone, Copy)]
+enum ReleaseAt {
+    Allocation(u64),
+    Time(Instant),
+}
+
+impl ReleaseAt {
+    fn has_expired(&self, allocations: u64, now: Instant) -> bool {
+        match self {
+            ReleaseAt::Allocation(release_at) => allocations >= *release_at,
+            ReleaseAt::Time(release_at) => now >= *release_at,
+        }
+    }
+}
+
+pub struct Quarantine<T: IdType> {
+    period: QuarantinePeriod,
+    ids: Intervals<T>,
+    queue: IntervalQueue<T, ReleaseAt>,
+}
+
+impl<T: IdType> Quarantine<T> {
+    pub fn new(period: QuarantinePeriod) -> Self {
+        Quarantine { period, ids: Intervals::new(), queue: IntervalQueue::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn dump(&self) -> String {
+        self.ids.dump()
+    }
+
+    pub fn count(&self) -> Option<u128> {
+        self.ids.count()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Interval<T>> {
+        self.ids.iter()
+    }
+
+    pub fn contains_any(&self, lower: T, upper: T) -> bool {
+        self.ids.contains_any(lower, upper)
+    }
+
+    pub fn gaps(&self, lower: T, upper: T) -> Vec<Interval<T>> {
+        self.ids.gaps(lower, upper)
+    }
+
+    pub fn insert(&mut self, lower: T, upper: T, allocations: u64, now: Instant) -> bool {
+        if !self.ids.insert_interval(lower, upper) {
+            return false;
+        }
+
+        let release_at = match self.period {
+            QuarantinePeriod::Allocations(count) => ReleaseAt::Allocation(allocations.saturating_add(count)),
+            QuarantinePeriod::Duration(duration) => ReleaseAt::Time(now + duration),
+        };
+
+        self.queue.push_back(lower, upper, release_at);
+
+        true
+    }
+
+    pub fn remove(&mut self, lower: T, upper: T) {
+        if !self.ids.contains_any(lower, upper) {
+            return;
+        }
+
+        self.ids.remove_interval(lower, upper);
+
+        self.queue.remove(lower, upper);
+    }
+
+    pub fn has_expired(&self, allocations: u64, now: Instant) -> bool {
+        match self.queue.front() {
+            Some((_, release_at)) => release_at.has_expired(allocations, now),
+            None => false,
+        }
+    }
+
+    pub fn release_expired(&mut self, allocations: u64, now: Instant) -> Vec<Interval<T>> {
+        let mut released = Vec::new();
+
+        while self.has_expired(allocations, now) {
+            let (interval, _) = self.queue.pop_front().unwrap();
+
+            self.ids.remove_interval(interval.lower(), interval.upper());
+
+            released.push(interval);
+        }
+
+        released
+    }
+
+    pub fn flush(&mut self) -> Vec<Interval<T>> {
+        self.ids = Intervals::new();
+
+        self.queue.clear()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_after_allocations() {
+        let mut quarantine = Quarantine::<u8>::new(QuarantinePeriod::Allocations(2));
+
+        let now = Instant::now();
+
+        assert_eq!(quarantine.insert(4, 4, 0, now), true);
+        assert_eq!(quarantine.insert(6, 8, 1, now), true);
+        assert_eq!(quarantine.insert(7, 7, 1, now), false);
+
+        assert_eq!(quarantine.dump(), "[4], [6,8]");
+
+        assert_eq!(quarantine.has_expired(1, now), false);
+        assert_eq!(quarantine.release_expired(1, now).len(), 0);
+
+        let released = quarantine.release_expired(2, now);
+
+        assert_eq!(released, vec![Interval::new(4, 4)]);
+
+        assert_eq!(quarantine.dump(), "[6,8]");
+
+        let released = quarantine.release_expired(3, now);
+
+        assert_eq!(released, vec![Interval::new(6, 8)]);
+
+        assert_eq!(quarantine.is_empty(), true);
+    }
+
+    #[test]
+    fn test_release_after_duration() {
+        let mut quarantine = Quarantine::<u8>::new(QuarantinePeriod::Duration(Duration::from_secs(10)));
+
+        let now = Instant::now();
+
+        assert_eq!(quarantine.insert(4, 4, 0, now), true);
+        assert_eq!(quarantine.insert(5, 5, 0, now + Duration::from_secs(5)), true);
+
+        assert_eq!(quarantine.release_expired(100, now + Duration::from_secs(9)).len(), 0);
+
+        assert_eq!(quarantine.release_expired(0, now + Duration::from_secs(10)), vec![Interval::new(4, 4)]);
+
+        assert_eq!(quarantine.release_expired(0, now + Duration::from_secs(15)), vec![Interval::new(5, 5)]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut quarantine = Quarantine::<u8>::new(QuarantinePeriod::Allocations(1));
+
+        let now = Instant::now();
+
+        assert_eq!(quarantine.insert(4, 10, 0, now), true);
+
+        quarantine.remove(6, 7);
+
+        assert_eq!(quarantine.dump(), "[4,5], [8,10]");
+
+        assert_eq!(quarantine.contains_any(6, 7), false);
+        assert_eq!(quarantine.contains_any(7, 8), true);
+
+        assert_eq!(quarantine.release_expired(1, now), vec![Interval::new(4, 5), Interval::new(8, 10)]);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut quarantine = Quarantine::<u8>::new(QuarantinePeriod::Allocations(100));
+
+        let now = Instant::now();
+
+        assert_eq!(quarantine.insert(4, 4, 0, now), true);
+        assert_eq!(quarantine.insert(2, 2, 0, now), true);
+
+        assert_eq!(quarantine.flush(), vec![Interval::new(4, 4), Interval::new(2, 2)]);
+
+        assert_eq!(quarantine.is_empty(), true);
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// SplitMix64, small and fast with good enough distribution for picking ids
+
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+
+        Random { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u128(&mut self) -> u128 {
+        ((self.next_u64() as u128) << 64) | self.next_u64() as u128
+    }
+
+    pub fn next_u128_at_most(&mut self, max: u128) -> u128 {
+        // mask off the unused high bits and retry until in range, this avoids modulo bias
+
+        let mask = u128::MAX.checked_shr(max.leading_zeros()).unwrap_or(0);
+
+        loop {
+            let value = self.next_u128() & mask;
+
+            if value <= max {
+                return value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_gives_same_sequence() {
+        let mut random1 = Random::new(Some(42));
+        let mut random2 = Random::new(Some(42));
+
+        for _ in 0..100 {
+            assert_eq!(random1.next_u64(), random2.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_next_u128_at_most() {
+        let mut random = Random::new(Some(42));
+
+        for _ in 0..1000 {
+            assert_eq!(random.next_u128_at_most(10) <= 10, true);
+        }
+
+        for _ in 0..100 {
+            assert_eq!(random.next_u128_at_most(0), 0);
+        }
+
+        let mut seen = [false; 4];
+
+        for _ in 0..100 {
+            seen[random.next_u128_at_most(3) as usize] = true;
+        }
+
+        assert_eq!(seen, [true; 4]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt;
+
+use crate::id_type::IdType;
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reservation<T: IdType> {
+    lower: T,
+    upper: T,
+    label: String,
+}
+
+impl<T: IdType> Reservation<T> {
+    pub fn new(lower: T, upper: T, label: &str) -> Self {
+        if upper < lower {
+            panic!("upper must be >= lower");
+        }
+
+        Reservation { lower, upper, label: label.to_string() }
+    }
+
+    pub fn lower(&self) -> T {
+        self.lower
+    }
+
+    pub fn upper(&self) -> T {
+        self.upper
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn overlaps(&self, lower: T, upper: T) -> bool {
+        self.lower <= upper && self.upper >= lower
+    }
+}
+
+impl<T: IdType> fmt::Display for Reservation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lower == self.upper {
+            write!(f, "[{}] {}", self.lower, self.label)
+        } else {
+            write!(f, "[{},{}] {}", self.lower, self.upper, self.label)
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let reservation = Reservation::<u8>::new(10, 20, "hardware");
+
+        assert_eq!(reservation.lower(), 10);
+
+        assert_eq!(reservation.upper(), 20);
+
+        assert_eq!(reservation.label(), "hardware");
+
+        assert_eq!(format!("{}", reservation), "[10,20] hardware");
+
+        assert_eq!(format!("{}", Reservation::<u8>::new(5, 5, "broadcast")), "[5] broadcast");
+    }
+
+    #[test]
+    #[should_panic(expected = "upper must be >= lower")]
+    fn test_new_invalid() {
+        let _reservation = Reservation::<u8>::new(20, 10, "hardware");
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let reservation = Reservation::<u8>::new(10, 20, "hardware");
+
+        assert_eq!(reservation.overlaps(0, 9), false);
+        assert_eq!(reservation.overlaps(0, 10), true);
+        assert_eq!(reservation.overlaps(15, 15), true);
+        assert_eq!(reservation.overlaps(20, 30), true);
+        assert_eq!(reservation.overlaps(21, 30), false);
+    }
+}
@@ -1,5 +1,10 @@
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ReusePolicy {
     ReuseFast,
     ReuseSlow,
+    ReuseFastHighest,
+    ReuseSlowDescending,
+    ReuseRandom { seed: Option<u64> },
+    ReuseOldestFreed,
 }
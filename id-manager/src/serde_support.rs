@@ -0,0 +1,205 @@
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::id_manager::IdManager;
+use crate::id_type::IdType;
+use crate::interval::Interval;
+use crate::intervals::Intervals;
+use crate::state::{IdManagerState, StateError};
+use crate::thread_safe_id_manager::ThreadSafeIdManager;
+
+// Interval::new() panics on invalid input, so deserialize into this and validate first
+
+#[derive(Serialize, Deserialize)]
+struct IntervalRepr<T> {
+    lower: T,
+    upper: T,
+}
+
+impl<T: IdType + Serialize> Serialize for Interval<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        IntervalRepr { lower: self.lower(), upper: self.upper() }.serialize(serializer)
+    }
+}
+
+impl<'de, T: IdType + Deserialize<'de>> Deserialize<'de> for Interval<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = IntervalRepr::<T>::deserialize(deserializer)?;
+
+        if repr.upper < repr.lower {
+            return Err(D::Error::custom(StateError::InvalidInterval));
+        }
+
+        Ok(Interval::new(repr.lower, repr.upper))
+    }
+}
+
+impl<T: IdType + Serialize> Serialize for Intervals<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T: IdType + Deserialize<'de>> Deserialize<'de> for Intervals<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let intervals = Vec::<Interval<T>>::deserialize(deserializer)?;
+
+        Intervals::try_from_sorted(&intervals).map_err(D::Error::custom)
+    }
+}
+
+impl<T: IdType + Serialize> Serialize for IdManager<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_state().map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de, T: IdType + Deserialize<'de>> Deserialize<'de> for IdManager<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = IdManagerState::<T>::deserialize(deserializer)?;
+
+        IdManager::from_state(state).map_err(D::Error::custom)
+    }
+}
+
+impl<T: IdType + Serialize> Serialize for ThreadSafeIdManager<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_state().map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de, T: IdType + Deserialize<'de>> Deserialize<'de> for ThreadSafeIdManager<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = IdManagerState::<T>::deserialize(deserializer)?;
+
+        ThreadSafeIdManager::from_state(state).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::allocation_strategy::FastStrategy;
+    use crate::reuse_policy::ReusePolicy::{ReuseFast, ReuseSlow};
+
+    use super::*;
+
+    #[test]
+    fn test_interval() {
+        let interval = Interval::<u8>::new(10, 20);
+
+        let json = serde_json::to_string(&interval).unwrap();
+
+        assert_eq!(json, r#"{"lower":10,"upper":20}"#);
+
+        assert_eq!(serde_json::from_str::<Interval<u8>>(&json).unwrap(), interval);
+
+        let error = serde_json::from_str::<Interval<u8>>(r#"{"lower":20,"upper":10}"#).unwrap_err();
+
+        assert_eq!(error.to_string(), "interval upper must be >= lower");
+    }
+
+    #[test]
+    fn test_intervals() {
+        let mut intervals = Intervals::<u8>::new();
+
+        intervals.insert_interval(0, 5);
+        intervals.insert_interval(10, 10);
+
+        let json = serde_json::to_string(&intervals).unwrap();
+
+        assert_eq!(json, r#"[{"lower":0,"upper":5},{"lower":10,"upper":10}]"#);
+
+        assert_eq!(serde_json::from_str::<Intervals<u8>>(&json).unwrap().dump(), "[0,5], [10]");
+
+        for invalid in [
+            r#"[{"lower":10,"upper":10},{"lower":0,"upper":5}]"#,
+            r#"[{"lower":0,"upper":5},{"lower":5,"upper":10}]"#,
+            r#"[{"lower":0,"upper":5},{"lower":6,"upper":10}]"#] {
+            let error = serde_json::from_str::<Intervals<u8>>(invalid).err().unwrap();
+
+            assert_eq!(error.to_string(), "intervals must be sorted, disjoint and non-adjacent");
+        }
+    }
+
+    #[test]
+    fn test_reuse_policy() {
+        let json = serde_json::to_string(&crate::reuse_policy::ReusePolicy::ReuseRandom { seed: Some(42) }).unwrap();
+
+        assert_eq!(json, r#"{"ReuseRandom":{"seed":42}}"#);
+
+        assert_eq!(serde_json::from_str::<crate::reuse_policy::ReusePolicy>(r#""ReuseSlow""#).unwrap(), ReuseSlow);
+    }
+
+    #[test]
+    fn test_id_manager_round_trip() {
+        let mut id_manager = IdManager::<u8>::new_limited_range(ReuseSlow, 10, 100);
+
+        id_manager.allocate();
+        id_manager.allocate();
+        id_manager.allocate();
+        id_manager.free(11);
+
+        id_manager.reserve(50, 59, "hardware").unwrap();
+
+        let json = serde_json::to_string(&id_manager).unwrap();
+
+        assert_eq!(json, r#"{"min_id":10,"max_id":100,"policy":"ReuseSlow","next_to_allocate":13,"ranges":[{"lower":10,"upper":100}],"free_ids":[{"lower":11,"upper":11},{"lower":13,"upper":49},{"lower":60,"upper":100}],"reservations":[{"lower":50,"upper":59,"label":"hardware"}]}"#);
+
+        let mut restored = serde_json::from_str::<IdManager<u8>>(&json).unwrap();
+
+        assert_eq!(restored.dump(), id_manager.dump());
+
+        assert_eq!(restored.reservations(), id_manager.reservations());
+
+        assert_eq!(restored.allocate(), 13);
+
+        assert_eq!(restored.try_free(50), Err(crate::id_error::IdError::Reserved));
+    }
+
+    #[test]
+    fn test_id_manager_validation() {
+        let valid = r#"{"min_id":10,"max_id":100,"policy":"ReuseFast","next_to_allocate":null,"ranges":[{"lower":10,"upper":100}],"free_ids":[{"lower":10,"upper":100}],"reservations":[]}"#;
+
+        assert!(serde_json::from_str::<IdManager<u8>>(valid).is_ok());
+
+        let out_of_range = valid.replace(r#""free_ids":[{"lower":10,"upper":100}]"#, r#""free_ids":[{"lower":5,"upper":100}]"#);
+
+        assert_eq!(serde_json::from_str::<IdManager<u8>>(&out_of_range).err().unwrap().to_string(), "interval is outside of the managed ranges");
+
+        let mismatch = valid.replace(r#""max_id":100"#, r#""max_id":200"#);
+
+        assert_eq!(serde_json::from_str::<IdManager<u8>>(&mismatch).err().unwrap().to_string(), "min_id and max_id must match the bounds of the ranges");
+
+        let no_ranges = valid.replace(r#""ranges":[{"lower":10,"upper":100}]"#, r#""ranges":[]"#);
+
+        assert_eq!(serde_json::from_str::<IdManager<u8>>(&no_ranges).err().unwrap().to_string(), "at least one range is required");
+
+        let reserved_free = valid.replace(r#""reservations":[]"#, r#""reservations":[{"lower":50,"upper":59,"label":"hardware"}]"#);
+
+        assert_eq!(serde_json::from_str::<IdManager<u8>>(&reserved_free).err().unwrap().to_string(), "reservation overlaps free ids or another reservation");
+    }
+
+    #[test]
+    fn test_custom_strategy_cannot_be_serialized() {
+        let id_manager = IdManager::<u8>::new(Box::new(FastStrategy));
+
+        assert_eq!(serde_json::to_string(&id_manager).unwrap_err().to_string(), "custom allocation strategies cannot be saved");
+    }
+
+    #[test]
+    fn test_thread_safe_id_manager_round_trip() {
+        let manager = ThreadSafeIdManager::<u16>::new_with_ranges(ReuseFast, &[0..=9, 100..=199]);
+
+        let _id = manager.allocate_id();
+
+        let json = serde_json::to_string(&manager).unwrap();
+
+        let restored = serde_json::from_str::<ThreadSafeIdManager<u16>>(&json).unwrap();
+
+        assert_eq!(restored.dump(), "[1,9], [100,199]");
+
+        assert_eq!(restored.is_allocated(0), true);
+    }
+}
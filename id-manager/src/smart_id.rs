@@ -1,31 +1,80 @@
 use std::fmt;
+use std::panic::Location;
 use std::sync::{Arc, Mutex};
 
+use crate::generational_id::GenerationalId;
+use crate::id_error::IdError;
 use crate::id_manager::IdManager;
 use crate::id_type::IdType;
 
 pub struct SmartId<T: IdType> {
     manager: Arc<Mutex<IdManager<T>>>,
     id: T,
+    generation: Option<u32>,
     we_own_id: bool,
 }
 
 impl<T: IdType> SmartId<T> {
+    #[track_caller]
     pub fn new(manager: Arc<Mutex<IdManager<T>>>) -> Self {
-        let mut locked = manager.lock().unwrap();
-
-        if !locked.can_allocate()
-        {
-            panic!("No Ids available")
+        match Self::try_new(manager) {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
         }
+    }
+
+    #[track_caller]
+    pub fn try_new(manager: Arc<Mutex<IdManager<T>>>) -> Result<Self, IdError> {
+        Self::try_new_with(manager, IdManager::try_allocate)
+    }
+
+    #[track_caller]
+    pub fn try_new_specific(manager: Arc<Mutex<IdManager<T>>>, id: T) -> Result<Self, IdError> {
+        Self::try_new_with(manager, |locked| locked.try_allocate_specific(id))
+    }
+
+    #[track_caller]
+    pub fn try_new_with<F>(manager: Arc<Mutex<IdManager<T>>>, allocate: F) -> Result<Self, IdError>
+        where F: FnOnce(&mut IdManager<T>) -> Result<T, IdError>
+    {
+        let location = Location::caller();
+
+        let id = {
+            let mut locked = manager.lock().map_err(|_| IdError::LockPoisoned)?;
+
+            let id = allocate(&mut locked)?;
+
+            locked.track_allocation(id, id, location);
 
-        let id = locked.allocate();
+            id
+        };
 
-        SmartId { manager : manager.clone(), id, we_own_id: true }
+        Ok(SmartId { manager, id, generation: None, we_own_id: true })
+    }
+
+    #[track_caller]
+    pub fn try_new_generational(manager: Arc<Mutex<IdManager<T>>>) -> Result<Self, IdError> {
+        let location = Location::caller();
+
+        let id = {
+            let mut locked = manager.lock().map_err(|_| IdError::LockPoisoned)?;
+
+            let id = locked.try_allocate_generational()?;
+
+            locked.track_allocation(id.index(), id.index(), location);
+
+            id
+        };
+
+        Ok(SmartId { manager, id: id.index(), generation: Some(id.generation()), we_own_id: true })
     }
 
     pub fn release(&mut self) -> T {
-        let _locked = self.manager.lock().unwrap();
+        let mut locked = self.manager.lock().unwrap_or_else(|error| error.into_inner());
+
+        if self.we_own_id {
+            locked.track_release(self.id);
+        }
 
         self.we_own_id = false;
 
@@ -35,6 +84,10 @@ impl<T: IdType> SmartId<T> {
     pub fn value(&self) -> &T {
         &self.id
     }
+
+    pub fn generational_id(&self) -> Option<GenerationalId<T>> {
+        self.generation.map(|generation| GenerationalId::new(self.id, generation))
+    }
 }
 
 impl<T: IdType> fmt::Display for SmartId< T> {
@@ -45,16 +98,23 @@ impl<T: IdType> fmt::Display for SmartId< T> {
 
 impl<T: IdType> Drop for SmartId<T> {
     fn drop(&mut self) {
-        let mut locked = self.manager.lock().unwrap();
+        // dropping must not panic, the lock may have been poisoned or the id freed directly
+        // through the manager, and a panic while unwinding would abort
+
+        let mut locked = self.manager.lock().unwrap_or_else(|error| error.into_inner());
 
         if self.we_own_id
         {
-            locked.free(self.id);
+            let _ = match self.generational_id() {
+                Some(id) => locked.try_free_generational(id),
+                None => locked.try_free(self.id),
+            };
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use crate::reuse_policy::ReusePolicy::ReuseSlow;
 
@@ -122,6 +182,84 @@ mod tests {
         assert_eq!(manager.lock().unwrap().dump(), "[0], [2,255]");
     }
 
+    #[test]
+    fn test_try_new_when_exhausted() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new_limited_range(ReuseSlow, 10, 10)));
+
+        let id1 = SmartId::<u8>::try_new(manager.clone()).unwrap();
+
+        assert_eq!(id1.value(), &10);
+
+        assert!(matches!(SmartId::<u8>::try_new(manager.clone()), Err(IdError::Exhausted)));
+    }
+
+    #[test]
+    #[should_panic(expected = "No Ids available")]
+    fn test_new_when_exhausted() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new_limited_range(ReuseSlow, 10, 10)));
+
+        let _id1 = SmartId::<u8>::new(manager.clone());
+
+        let _id2 = SmartId::<u8>::new(manager.clone());
+    }
+
+    #[test]
+    fn test_try_new_specific() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        {
+            let id1 = SmartId::<u8>::try_new_specific(manager.clone(), 10).unwrap();
+
+            assert_eq!(id1.value(), &10);
+
+            assert_eq!(manager.lock().unwrap().dump(), "[0,9], [11,255]");
+
+            assert!(matches!(SmartId::<u8>::try_new_specific(manager.clone(), 10), Err(IdError::AlreadyAllocated)));
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_try_new_generational() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new_limited_range(ReuseSlow, 10, 10)));
+
+        assert!(matches!(SmartId::<u8>::try_new_generational(manager.clone()), Err(IdError::GenerationsDisabled)));
+
+        manager.lock().unwrap().enable_generations();
+
+        let first = {
+            let id1 = SmartId::<u8>::try_new_generational(manager.clone()).unwrap();
+
+            assert_eq!(id1.generational_id(), Some(GenerationalId::new(10, 0)));
+
+            assert_eq!(manager.lock().unwrap().is_current(id1.generational_id().unwrap()), true);
+
+            id1.generational_id().unwrap()
+        };
+
+        assert_eq!(manager.lock().unwrap().is_current(first), false);
+
+        let id2 = SmartId::<u8>::try_new_generational(manager.clone()).unwrap();
+
+        assert_eq!(id2.generational_id(), Some(GenerationalId::new(10, 1)));
+
+        assert_eq!(manager.lock().unwrap().is_current(first), false);
+
+        assert_eq!(manager.lock().unwrap().try_free_generational(first), Err(IdError::StaleGeneration));
+    }
+
+    #[test]
+    fn test_generational_id_is_none_when_not_generational() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        manager.lock().unwrap().enable_generations();
+
+        let id1 = SmartId::<u8>::new(manager.clone());
+
+        assert_eq!(id1.generational_id(), None);
+    }
+
     #[test]
     fn test_release() {
         let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
@@ -140,4 +278,36 @@ mod tests {
 
         assert_eq!(manager.lock().unwrap().dump(), "[1,255]");
     }
+
+    #[test]
+    fn test_drop_after_id_freed_through_manager() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        {
+            let id1 = SmartId::<u8>::new(manager.clone());
+
+            manager.lock().unwrap().free(*id1.value());
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_drop_when_lock_poisoned() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        let id1 = SmartId::<u8>::new(manager.clone());
+
+        let poisoner = manager.clone();
+
+        let _ = std::thread::spawn(move || {
+            let _locked = poisoner.lock();
+
+            panic!("poison the lock");
+        }).join();
+
+        drop(id1);
+
+        assert_eq!(manager.lock().unwrap_or_else(|error| error.into_inner()).dump(), "[0,255]");
+    }
 }
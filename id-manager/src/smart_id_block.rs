@@ -0,0 +1,235 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+use crate::id_error::IdError;
+use crate::id_manager::IdManager;
+use crate::id_type::IdType;
+
+pub struct SmartIdBlock<T: IdType> {
+    manager: Arc<Mutex<IdManager<T>>>,
+    lower: T,
+    upper: T,
+    we_own_ids: bool,
+}
+
+impl<T: IdType> SmartIdBlock<T> {
+    #[track_caller]
+    pub fn new(manager: Arc<Mutex<IdManager<T>>>, count: T) -> Self {
+        match Self::try_new(manager, count) {
+            Ok(block) => block,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[track_caller]
+    pub fn try_new(manager: Arc<Mutex<IdManager<T>>>, count: T) -> Result<Self, IdError> {
+        let location = Location::caller();
+
+        let block = {
+            let mut locked = manager.lock().map_err(|_| IdError::LockPoisoned)?;
+
+            let block = locked.try_allocate_block(count)?;
+
+            locked.track_allocation(block.lower(), block.upper(), location);
+
+            block
+        };
+
+        Ok(SmartIdBlock { manager, lower: block.lower(), upper: block.upper(), we_own_ids: true })
+    }
+
+    #[track_caller]
+    pub fn new_aligned(manager: Arc<Mutex<IdManager<T>>>, count: T, alignment: T) -> Self {
+        match Self::try_new_aligned(manager, count, alignment) {
+            Ok(block) => block,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[track_caller]
+    pub fn try_new_aligned(manager: Arc<Mutex<IdManager<T>>>, count: T, alignment: T) -> Result<Self, IdError> {
+        let location = Location::caller();
+
+        let block = {
+            let mut locked = manager.lock().map_err(|_| IdError::LockPoisoned)?;
+
+            let block = locked.try_allocate_aligned(count, alignment)?;
+
+            locked.track_allocation(block.lower(), block.upper(), location);
+
+            block
+        };
+
+        Ok(SmartIdBlock { manager, lower: block.lower(), upper: block.upper(), we_own_ids: true })
+    }
+
+    pub fn release(&mut self) -> RangeInclusive<T> {
+        let mut locked = self.manager.lock().unwrap_or_else(|error| error.into_inner());
+
+        if self.we_own_ids {
+            locked.track_release(self.lower);
+        }
+
+        self.we_own_ids = false;
+
+        self.lower..=self.upper
+    }
+
+    pub fn lower(&self) -> T {
+        self.lower
+    }
+
+    pub fn upper(&self) -> T {
+        self.upper
+    }
+
+    pub fn contains_value(&self, value: T) -> bool {
+        value >= self.lower && value <= self.upper
+    }
+}
+
+impl<T: IdType> fmt::Display for SmartIdBlock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lower == self.upper {
+            write!(f, "[{}]", self.lower)
+        } else {
+            write!(f, "[{},{}]", self.lower, self.upper)
+        }
+    }
+}
+
+impl<T: IdType> Drop for SmartIdBlock<T> {
+    fn drop(&mut self) {
+        // as with SmartId, dropping must not panic. the block is freed in one go unless some of
+        // it was already freed through the manager, then the rest is freed id by id
+
+        let mut locked = self.manager.lock().unwrap_or_else(|error| error.into_inner());
+
+        if self.we_own_ids && locked.try_free_interval(self.lower, self.upper).is_err()
+        {
+            let mut id = self.lower;
+
+            loop {
+                let _ = locked.try_free(id);
+
+                if id == self.upper {
+                    break;
+                }
+
+                id = id + T::one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reuse_policy::ReusePolicy::ReuseSlow;
+
+    use super::*;
+
+    #[test]
+    fn test_create_one_block() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        assert_eq!(manager.lock().unwrap().dump(), "[0,255]");
+
+        {
+            let block = SmartIdBlock::<u8>::new(manager.clone(), 10);
+
+            assert_eq!(block.lower(), 0);
+            assert_eq!(block.upper(), 9);
+
+            assert_eq!(format!("{}", block), "[0,9]");
+
+            assert_eq!(manager.lock().unwrap().dump(), "[10,255]");
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_create_multiple_blocks() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        {
+            let block1 = SmartIdBlock::<u8>::new(manager.clone(), 10);
+
+            assert_eq!(format!("{}", block1), "[0,9]");
+
+            {
+                let block2 = SmartIdBlock::<u8>::new(manager.clone(), 1);
+
+                assert_eq!(format!("{}", block2), "[10]");
+
+                assert_eq!(manager.lock().unwrap().dump(), "[11,255]");
+            }
+
+            assert_eq!(manager.lock().unwrap().dump(), "[10,255]");
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_try_new_when_no_block_large_enough() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new_limited_range(ReuseSlow, 10, 19)));
+
+        manager.lock().unwrap().mark_value_as_used(15);
+
+        assert!(matches!(SmartIdBlock::<u8>::try_new(manager.clone(), 6), Err(IdError::NoSuitableBlock)));
+
+        let block = SmartIdBlock::<u8>::try_new(manager.clone(), 5).unwrap();
+
+        assert_eq!(format!("{}", block), "[10,14]");
+
+        assert_eq!(manager.lock().unwrap().dump(), "[16,19]");
+    }
+
+    #[test]
+    fn test_create_aligned_block() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        manager.lock().unwrap().mark_value_as_used(0);
+
+        {
+            let block = SmartIdBlock::<u8>::new_aligned(manager.clone(), 64, 64);
+
+            assert_eq!(format!("{}", block), "[64,127]");
+
+            assert_eq!(manager.lock().unwrap().dump(), "[1,63], [128,255]");
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[1,255]");
+    }
+
+    #[test]
+    fn test_release() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        {
+            let mut block = SmartIdBlock::<u8>::new(manager.clone(), 5);
+
+            assert_eq!(block.release(), 0..=4);
+
+            assert_eq!(manager.lock().unwrap().dump(), "[5,255]");
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[5,255]");
+    }
+
+    #[test]
+    fn test_drop_after_ids_freed_through_manager() {
+        let manager = Arc::new(Mutex::new(IdManager::<u8>::new(ReuseSlow)));
+
+        {
+            let _block = SmartIdBlock::<u8>::new(manager.clone(), 5);
+
+            manager.lock().unwrap().free(2);
+        }
+
+        assert_eq!(manager.lock().unwrap().dump(), "[0,255]");
+    }
+}
@@ -0,0 +1,529 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+
+use crate::id_type::IdType;
+use crate::interval::Interval;
+use crate::reservation::Reservation;
+use crate::reuse_policy::ReusePolicy;
+use crate::state::{IdManagerState, StateError};
+
+// a snapshot is laid out as:
+//
+//   magic "IDMS", version: u8, type width in bytes: u8, body length: u32 LE
+//   body
+//   CRC-32 of everything before it: u32 LE
+//
+// the body holds the policy, the cursor, then the ranges, free ids and reservations. values are
+// LEB128 varints and each interval list is delta encoded, the first lower is absolute, each later
+// lower is the gap after the previous upper and each upper is stored as its distance from its lower.
+
+const MAGIC: &[u8; 4] = b"IDMS";
+
+const VERSION: u8 = 1;
+
+const HEADER_LENGTH: usize = 10;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    TypeWidthMismatch { expected: u8, found: u8 },
+    ChecksumMismatch { expected: u32, found: u32 },
+    UnknownPolicy(u8),
+    InvalidVarint,
+    ValueOutOfRange,
+    InvalidLabel,
+    TrailingData,
+    TooLarge,
+    InvalidState(StateError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "snapshot i/o error: {}", error),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::BadMagic => write!(f, "not an id manager snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(f, "unsupported snapshot version {}", version),
+            SnapshotError::TypeWidthMismatch { expected, found } => write!(f, "snapshot is for {} byte ids, expected {} byte ids", found, expected),
+            SnapshotError::ChecksumMismatch { expected, found } => write!(f, "snapshot checksum mismatch, expected {:08x} found {:08x}", expected, found),
+            SnapshotError::UnknownPolicy(policy) => write!(f, "unknown reuse policy {} in snapshot", policy),
+            SnapshotError::InvalidVarint => write!(f, "invalid varint in snapshot"),
+            SnapshotError::ValueOutOfRange => write!(f, "snapshot value does not fit the id type"),
+            SnapshotError::InvalidLabel => write!(f, "snapshot reservation label is not valid utf-8"),
+            SnapshotError::TrailingData => write!(f, "snapshot body has trailing data"),
+            SnapshotError::TooLarge => write!(f, "snapshot body is too large"),
+            SnapshotError::InvalidState(error) => write!(f, "invalid snapshot state: {}", error),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(error) => Some(error),
+            SnapshotError::InvalidState(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            SnapshotError::Truncated
+        } else {
+            SnapshotError::Io(error)
+        }
+    }
+}
+
+impl From<StateError> for SnapshotError {
+    fn from(error: StateError) -> Self {
+        SnapshotError::InvalidState(error)
+    }
+}
+
+pub fn write_snapshot<T: IdType>(state: &IdManagerState<T>, mut writer: impl Write) -> Result<(), SnapshotError> {
+    let mut body = Vec::new();
+
+    write_policy(&mut body, state.policy);
+
+    match state.next_to_allocate {
+        Some(cursor) => {
+            body.push(1);
+
+            write_varint(&mut body, to_u128(cursor));
+        }
+        None => body.push(0),
+    }
+
+    write_intervals(&mut body, &state.ranges);
+
+    write_intervals(&mut body, &state.free_ids);
+
+    write_varint(&mut body, state.reservations.len() as u128);
+
+    for reservation in &state.reservations {
+        write_varint(&mut body, to_u128(reservation.lower()));
+        write_varint(&mut body, to_u128(reservation.upper()) - to_u128(reservation.lower()));
+        write_varint(&mut body, reservation.label().len() as u128);
+
+        body.extend_from_slice(reservation.label().as_bytes());
+    }
+
+    let body_length = u32::try_from(body.len()).map_err(|_| SnapshotError::TooLarge)?;
+
+    let mut snapshot = Vec::with_capacity(HEADER_LENGTH + body.len() + 4);
+
+    snapshot.extend_from_slice(MAGIC);
+    snapshot.push(VERSION);
+    snapshot.push(size_of::<T>() as u8);
+    snapshot.extend_from_slice(&body_length.to_le_bytes());
+    snapshot.extend_from_slice(&body);
+
+    let checksum = crc32(&snapshot);
+
+    snapshot.extend_from_slice(&checksum.to_le_bytes());
+
+    writer.write_all(&snapshot)?;
+
+    Ok(())
+}
+
+// reads exactly one snapshot, so a snapshot can be followed by other data in the same stream
+
+pub fn read_snapshot<T: IdType>(mut reader: impl Read) -> Result<IdManagerState<T>, SnapshotError> {
+    let mut header = [0u8; HEADER_LENGTH];
+
+    reader.read_exact(&mut header)?;
+
+    if &header[0..4] != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+
+    if header[4] != VERSION {
+        return Err(SnapshotError::UnsupportedVersion(header[4]));
+    }
+
+    let width = size_of::<T>() as u8;
+
+    if header[5] != width {
+        return Err(SnapshotError::TypeWidthMismatch { expected: width, found: header[5] });
+    }
+
+    let body_length = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+
+    // read_exact into a sized buffer would let a corrupt length allocate up to 4GB before the checksum is checked
+
+    let mut body = Vec::new();
+
+    reader.by_ref().take(body_length as u64).read_to_end(&mut body)?;
+
+    if body.len() != body_length {
+        return Err(SnapshotError::Truncated);
+    }
+
+    let mut checksum = [0u8; 4];
+
+    reader.read_exact(&mut checksum)?;
+
+    let expected = u32::from_le_bytes(checksum);
+
+    let found = crc32(&[&header[..], &body[..]].concat());
+
+    if expected != found {
+        return Err(SnapshotError::ChecksumMismatch { expected, found });
+    }
+
+    let mut body = Body::new(&body);
+
+    let policy = body.read_policy()?;
+
+    let next_to_allocate = match body.read_byte()? {
+        0 => None,
+        _ => Some(body.read_value()?),
+    };
+
+    let ranges = body.read_intervals()?;
+
+    let free_ids = body.read_intervals()?;
+
+    let count = body.read_count()?;
+
+    let mut reservations = Vec::new();
+
+    for _ in 0..count {
+        let lower = body.read_varint()?;
+        let upper = lower.checked_add(body.read_varint()?).ok_or(SnapshotError::ValueOutOfRange)?;
+        let label_length = body.read_count()?;
+        let label = std::str::from_utf8(body.read_bytes(label_length)?).map_err(|_| SnapshotError::InvalidLabel)?;
+
+        reservations.push(Reservation::new(from_u128(lower)?, from_u128(upper)?, label));
+    }
+
+    if body.position != body.data.len() {
+        return Err(SnapshotError::TrailingData);
+    }
+
+    let (min_id, max_id) = match (ranges.first(), ranges.last()) {
+        (Some(first), Some(last)) => (first.lower(), last.upper()),
+        _ => return Err(SnapshotError::InvalidState(StateError::NoRanges)),
+    };
+
+    Ok(IdManagerState { min_id, max_id, policy, next_to_allocate, ranges, free_ids, reservations })
+}
+
+fn write_policy(body: &mut Vec<u8>, policy: ReusePolicy) {
+    match policy {
+        ReusePolicy::ReuseFast => body.push(0),
+        ReusePolicy::ReuseSlow => body.push(1),
+        ReusePolicy::ReuseFastHighest => body.push(2),
+        ReusePolicy::ReuseSlowDescending => body.push(3),
+        ReusePolicy::ReuseRandom { seed: None } => body.push(4),
+        ReusePolicy::ReuseRandom { seed: Some(seed) } => {
+            body.push(5);
+
+            body.extend_from_slice(&seed.to_le_bytes());
+        }
+        ReusePolicy::ReuseOldestFreed => body.push(6),
+    }
+}
+
+fn write_intervals<T: IdType>(body: &mut Vec<u8>, intervals: &[Interval<T>]) {
+    write_varint(body, intervals.len() as u128);
+
+    let mut next: u128 = 0;
+
+    for interval in intervals {
+        let lower = to_u128(interval.lower());
+        let upper = to_u128(interval.upper());
+
+        write_varint(body, lower - next);
+        write_varint(body, upper - lower);
+
+        // wraps only after an interval that ends at u128::MAX, which must be the last one
+
+        next = upper.wrapping_add(1);
+    }
+}
+
+pub fn write_varint(body: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        body.push((value as u8 & 0x7f) | 0x80);
+
+        value >>= 7;
+    }
+
+    body.push(value as u8);
+}
+
+pub fn to_u128<T: IdType>(value: T) -> u128 {
+    value.to_u128().unwrap()
+}
+
+pub fn from_u128<T: IdType>(value: u128) -> Result<T, SnapshotError> {
+    T::from_u128(value).ok_or(SnapshotError::ValueOutOfRange)
+}
+
+pub struct Body<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Body<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Body { data, position: 0 }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.data.len() - self.position < count {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let bytes = &self.data[self.position..self.position + count];
+
+        self.position += count;
+
+        Ok(bytes)
+    }
+
+    pub fn read_byte(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_varint(&mut self) -> Result<u128, SnapshotError> {
+        let mut value: u128 = 0;
+
+        for shift in (0..128).step_by(7) {
+            let byte = self.read_byte()?;
+
+            let bits = (byte & 0x7f) as u128;
+
+            if shift == 126 && bits > 0x03 {
+                return Err(SnapshotError::InvalidVarint);
+            }
+
+            value |= bits << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(SnapshotError::InvalidVarint)
+    }
+
+    pub fn read_value<T: IdType>(&mut self) -> Result<T, SnapshotError> {
+        from_u128(self.read_varint()?)
+    }
+
+    // counts can't exceed the remaining bytes, this stops a corrupt count from causing a huge allocation
+
+    fn read_count(&mut self) -> Result<usize, SnapshotError> {
+        let count = self.read_varint()?;
+
+        if count > (self.data.len() - self.position) as u128 {
+            return Err(SnapshotError::Truncated);
+        }
+
+        Ok(count as usize)
+    }
+
+    fn read_policy(&mut self) -> Result<ReusePolicy, SnapshotError> {
+        match self.read_byte()? {
+            0 => Ok(ReusePolicy::ReuseFast),
+            1 => Ok(ReusePolicy::ReuseSlow),
+            2 => Ok(ReusePolicy::ReuseFastHighest),
+            3 => Ok(ReusePolicy::ReuseSlowDescending),
+            4 => Ok(ReusePolicy::ReuseRandom { seed: None }),
+            5 => {
+                let seed = self.read_bytes(8)?;
+
+                Ok(ReusePolicy::ReuseRandom { seed: Some(u64::from_le_bytes(seed.try_into().unwrap())) })
+            }
+            6 => Ok(ReusePolicy::ReuseOldestFreed),
+            policy => Err(SnapshotError::UnknownPolicy(policy)),
+        }
+    }
+
+    fn read_intervals<T: IdType>(&mut self) -> Result<Vec<Interval<T>>, SnapshotError> {
+        let count = self.read_count()?;
+
+        let mut intervals = Vec::with_capacity(count);
+
+        let mut next: Option<u128> = Some(0);
+
+        for _ in 0..count {
+            // an interval after one that ended at u128::MAX can't be represented
+
+            let gap = self.read_varint()?;
+
+            let lower = next.and_then(|next| next.checked_add(gap)).ok_or(SnapshotError::ValueOutOfRange)?;
+            let upper = lower.checked_add(self.read_varint()?).ok_or(SnapshotError::ValueOutOfRange)?;
+
+            intervals.push(Interval::new(from_u128(lower)?, from_u128(upper)?));
+
+            next = upper.checked_add(1);
+        }
+
+        Ok(intervals)
+    }
+}
+
+// CRC-32 (IEEE 802.3), bitwise as snapshots are written rarely
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> IdManagerState<u64> {
+        IdManagerState {
+            min_id: 0,
+            max_id: u64::MAX,
+            policy: ReusePolicy::ReuseRandom { seed: Some(42) },
+            next_to_allocate: Some(1000),
+            ranges: vec![Interval::new(0, u64::MAX)],
+            free_ids: vec![Interval::new(1, 1), Interval::new(3, 999), Interval::new(1001, u64::MAX)],
+            reservations: vec![Reservation::new(1000, 1000, "hardware")],
+        }
+    }
+
+    fn snapshot() -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        write_snapshot(&state(), &mut buffer).unwrap();
+
+        buffer
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let buffer = snapshot();
+
+        assert_eq!(read_snapshot::<u64>(&buffer[..]).unwrap(), state());
+    }
+
+    #[test]
+    fn test_fragmented_pool_is_compact() {
+        let free_ids: Vec<Interval<u64>> = (0..1000u64).map(|i| Interval::new(1_000_000_000 + i * 3, 1_000_000_000 + i * 3 + 1)).collect();
+
+        let state = IdManagerState::<u64> { min_id: 0, max_id: u64::MAX, policy: ReusePolicy::ReuseFast, next_to_allocate: None, ranges: vec![Interval::new(0, u64::MAX)], free_ids, reservations: vec![] };
+
+        let mut buffer = Vec::new();
+
+        write_snapshot(&state, &mut buffer).unwrap();
+
+        assert!(buffer.len() < 2100);
+
+        assert_eq!(read_snapshot::<u64>(&buffer[..]).unwrap(), state);
+    }
+
+    #[test]
+    fn test_snapshot_can_be_followed_by_other_data() {
+        let mut buffer = snapshot();
+
+        buffer.extend_from_slice(b"more");
+
+        let mut reader = &buffer[..];
+
+        assert_eq!(read_snapshot::<u64>(&mut reader).unwrap(), state());
+
+        assert_eq!(reader, b"more");
+    }
+
+    #[test]
+    fn test_truncated() {
+        let buffer = snapshot();
+
+        for length in 0..buffer.len() {
+            assert!(matches!(read_snapshot::<u64>(&buffer[..length]), Err(SnapshotError::Truncated)), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let buffer = snapshot();
+
+        for position in HEADER_LENGTH..buffer.len() {
+            let mut corrupted = buffer.clone();
+
+            corrupted[position] ^= 0x01;
+
+            assert!(matches!(read_snapshot::<u64>(&corrupted[..]), Err(SnapshotError::ChecksumMismatch { .. })), "position {}", position);
+        }
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let buffer = snapshot();
+
+        let mut corrupted = buffer.clone();
+
+        corrupted[0] = b'X';
+
+        assert!(matches!(read_snapshot::<u64>(&corrupted[..]), Err(SnapshotError::BadMagic)));
+
+        let mut corrupted = buffer.clone();
+
+        corrupted[4] = 2;
+
+        assert!(matches!(read_snapshot::<u64>(&corrupted[..]), Err(SnapshotError::UnsupportedVersion(2))));
+
+        let error = read_snapshot::<u32>(&buffer[..]).unwrap_err();
+
+        assert!(matches!(error, SnapshotError::TypeWidthMismatch { expected: 4, found: 8 }));
+
+        assert_eq!(error.to_string(), "snapshot is for 8 byte ids, expected 4 byte ids");
+    }
+
+    #[test]
+    fn test_u128_extremes() {
+        let state = IdManagerState::<u128> {
+            min_id: 0,
+            max_id: u128::MAX,
+            policy: ReusePolicy::ReuseSlowDescending,
+            next_to_allocate: Some(u128::MAX),
+            ranges: vec![Interval::new(0, u128::MAX)],
+            free_ids: vec![Interval::new(0, 0), Interval::new(u128::MAX - 1, u128::MAX)],
+            reservations: vec![],
+        };
+
+        let mut buffer = Vec::new();
+
+        write_snapshot(&state, &mut buffer).unwrap();
+
+        assert_eq!(read_snapshot::<u128>(&buffer[..]).unwrap(), state);
+    }
+}
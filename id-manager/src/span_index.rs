@@ -0,0 +1,267 @@
+use std::cmp::Ordering;
+
+use crate::id_type::IdType;
+
+// a treap of intervals keyed by their lower and upper values, where each node also holds the
+// total span and node count of its subtree, so that the interval holding the nth value can be
+// found in O(log n). priorities are a hash of the key, so the shape is deterministic but still
+// balanced.
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T: IdType> {
+    lower: T,
+    upper: T,
+    span: u128,
+    priority: u64,
+    total_span: u128,
+    nodes: u128,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T: IdType> Node<T> {
+    fn new(lower: T, upper: T, span: u128) -> Self {
+        Node { lower, upper, span, priority: Self::priority_of(lower, upper), total_span: span, nodes: 1, left: None, right: None }
+    }
+
+    // a SplitMix64 finaliser over the bounds, only needs to be well spread, not unpredictable
+
+    fn priority_of(lower: T, upper: T) -> u64 {
+        let value = lower.to_u128().unwrap() ^ upper.to_u128().unwrap().rotate_left(64);
+
+        let mut z = (value as u64) ^ ((value >> 64) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    // the number of values in the subtree, this can't be every u128 value as the caller always
+    // has a node outside of the subtree, so it fits
+
+    fn weight(node: &Link<T>) -> u128 {
+        match node {
+            Some(node) => node.total_span + node.nodes,
+            None => 0,
+        }
+    }
+
+    fn key(&self) -> (T, T) {
+        (self.lower, self.upper)
+    }
+
+    fn update(&mut self) {
+        self.total_span = self.span;
+        self.nodes = 1;
+
+        for child in [&self.left, &self.right].into_iter().flatten() {
+            self.total_span = self.total_span.wrapping_add(child.total_span);
+            self.nodes += child.nodes;
+        }
+    }
+}
+
+pub struct SpanIndex<T: IdType> {
+    root: Link<T>,
+}
+
+impl<T: IdType> SpanIndex<T> {
+    pub fn new() -> Self {
+        SpanIndex { root: None }
+    }
+
+    // intervals are inserted before the one they're split from is removed, so the key has to
+    // include the upper value as the lower value alone isn't unique
+
+    pub fn insert(&mut self, lower: T, upper: T, span: u128) {
+        let (left, right) = Self::split(self.root.take(), (lower, upper));
+
+        let node = Some(Box::new(Node::new(lower, upper, span)));
+
+        self.root = Self::merge(Self::merge(left, node), right);
+    }
+
+    pub fn remove(&mut self, lower: T, upper: T) {
+        self.root = Self::remove_from(self.root.take(), (lower, upper));
+    }
+
+    // the offset of the highest value present, this always fits as it's one less than the
+    // number of values, even when every u128 value is present
+
+    pub fn last_offset(&self) -> Option<u128> {
+        self.root.as_ref().map(|root| root.total_span + (root.nodes - 1))
+    }
+
+    // the lower value of the interval that holds the value at offset, counting from the lowest
+    // value present, and the offset of that value within its interval
+
+    pub fn find(&self, mut offset: u128) -> Option<(T, u128)> {
+        let mut current = self.root.as_ref();
+
+        while let Some(node) = current {
+            let left_weight = Node::weight(&node.left);
+
+            if offset < left_weight {
+                current = node.left.as_ref();
+
+                continue;
+            }
+
+            offset -= left_weight;
+
+            if offset <= node.span {
+                return Some((node.lower, offset));
+            }
+
+            offset -= node.span + 1;
+
+            current = node.right.as_ref();
+        }
+
+        None
+    }
+
+    // splits into the nodes below key and the nodes at or above it
+
+    fn split(node: Link<T>, key: (T, T)) -> (Link<T>, Link<T>) {
+        match node {
+            None => (None, None),
+            Some(mut node) => {
+                if node.key() < key {
+                    let (left, right) = Self::split(node.right.take(), key);
+
+                    node.right = left;
+                    node.update();
+
+                    (Some(node), right)
+                } else {
+                    let (left, right) = Self::split(node.left.take(), key);
+
+                    node.left = right;
+                    node.update();
+
+                    (left, Some(node))
+                }
+            }
+        }
+    }
+
+    // every node in left must be below every node in right
+
+    fn merge(left: Link<T>, right: Link<T>) -> Link<T> {
+        match (left, right) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(mut left), Some(mut right)) => {
+                if left.priority >= right.priority {
+                    left.right = Self::merge(left.right.take(), Some(right));
+                    left.update();
+
+                    Some(left)
+                } else {
+                    right.left = Self::merge(Some(left), right.left.take());
+                    right.update();
+
+                    Some(right)
+                }
+            }
+        }
+    }
+
+    fn remove_from(node: Link<T>, key: (T, T)) -> Link<T> {
+        let mut node = node?;
+
+        match key.cmp(&node.key()) {
+            Ordering::Less => node.left = Self::remove_from(node.left.take(), key),
+            Ordering::Greater => node.right = Self::remove_from(node.right.take(), key),
+            Ordering::Equal => return Self::merge(node.left.take(), node.right.take()),
+        }
+
+        node.update();
+
+        Some(node)
+    }
+}
+
+impl<T: IdType> Default for SpanIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let mut index = SpanIndex::<u8>::new();
+
+        assert_eq!(index.find(0), None);
+        assert_eq!(index.last_offset(), None);
+
+        index.insert(10, 12, 2);
+        index.insert(0, 0, 0);
+        index.insert(20, 20, 0);
+
+        assert_eq!(index.find(0), Some((0, 0)));
+        assert_eq!(index.find(1), Some((10, 0)));
+        assert_eq!(index.find(3), Some((10, 2)));
+        assert_eq!(index.find(4), Some((20, 0)));
+        assert_eq!(index.find(5), None);
+        assert_eq!(index.last_offset(), Some(4));
+
+        index.remove(10, 12);
+
+        assert_eq!(index.find(1), Some((20, 0)));
+        assert_eq!(index.find(2), None);
+
+        index.remove(99, 99);
+
+        assert_eq!(index.find(1), Some((20, 0)));
+    }
+
+    #[test]
+    fn test_find_many() {
+        let mut index = SpanIndex::<u32>::new();
+
+        for i in 0..10000u32 {
+            index.insert(i * 10, i * 10 + 4, 4);
+        }
+
+        for i in (0..10000u32).step_by(2) {
+            index.remove(i * 10, i * 10 + 4);
+        }
+
+        assert_eq!(index.find(0), Some((10, 0)));
+        assert_eq!(index.find(7), Some((30, 2)));
+        assert_eq!(index.find(5 * 4999 + 4), Some((99990, 4)));
+        assert_eq!(index.find(5 * 5000), None);
+    }
+
+    #[test]
+    fn test_split_interval_with_same_lower() {
+        let mut index = SpanIndex::<u8>::new();
+
+        index.insert(10, 20, 10);
+        index.insert(10, 14, 4);
+        index.insert(16, 20, 4);
+        index.remove(10, 20);
+
+        assert_eq!(index.find(4), Some((10, 4)));
+        assert_eq!(index.find(5), Some((16, 0)));
+        assert_eq!(index.find(10), None);
+    }
+
+    #[test]
+    fn test_every_u128_value() {
+        let mut index = SpanIndex::<u128>::new();
+
+        index.insert(0, u128::MAX, u128::MAX);
+
+        assert_eq!(index.find(u128::MAX), Some((0, u128::MAX)));
+        assert_eq!(index.last_offset(), Some(u128::MAX));
+    }
+}
@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::id_type::IdType;
+use crate::interval::Interval;
+use crate::reservation::Reservation;
+use crate::reuse_policy::ReusePolicy;
+
+// the persistent state of an IdManager, quarantined ids are saved as free and observers, leases,
+// owners, generations and leak detection are runtime only and are not saved
+
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdManagerState<T: IdType> {
+    pub min_id: T,
+    pub max_id: T,
+    pub policy: ReusePolicy,
+    pub next_to_allocate: Option<T>,
+    pub ranges: Vec<Interval<T>>,
+    pub free_ids: Vec<Interval<T>>,
+    pub reservations: Vec<Reservation<T>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StateError {
+    CustomStrategy,
+    NoRanges,
+    InvalidInterval,
+    UnorderedIntervals,
+    RangeMismatch,
+    OutOfRange,
+    ReservationNotAllocated,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::CustomStrategy => write!(f, "custom allocation strategies cannot be saved"),
+            StateError::NoRanges => write!(f, "at least one range is required"),
+            StateError::InvalidInterval => write!(f, "interval upper must be >= lower"),
+            StateError::UnorderedIntervals => write!(f, "intervals must be sorted, disjoint and non-adjacent"),
+            StateError::RangeMismatch => write!(f, "min_id and max_id must match the bounds of the ranges"),
+            StateError::OutOfRange => write!(f, "interval is outside of the managed ranges"),
+            StateError::ReservationNotAllocated => write!(f, "reservation overlaps free ids or another reservation"),
+        }
+    }
+}
+
+impl Error for StateError {}
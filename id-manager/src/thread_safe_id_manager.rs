@@ -1,9 +1,28 @@
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
+use crate::allocation_strategy::IntoAllocationStrategy;
+use crate::clock::Clock;
+use crate::direction::Direction;
+use crate::generational_id::GenerationalId;
+use crate::id_error::{IdError, ShrinkRangeError};
 use crate::id_manager::IdManager;
 use crate::id_type::IdType;
-use crate::reuse_policy::ReusePolicy;
+use crate::interval::Interval;
+use crate::journal::JournalError;
+use crate::lease::Lease;
+use crate::leak_detection::TrackedAllocation;
+use crate::observer::IdManagerObserver;
+use crate::quarantine::QuarantinePeriod;
+use crate::reservation::Reservation;
+use crate::state::{IdManagerState, StateError};
 use crate::smart_id::SmartId;
+use crate::snapshot::SnapshotError;
+use crate::smart_id_block::SmartIdBlock;
+use crate::values::Values;
 
 #[derive(Clone)]
 pub struct ThreadSafeIdManager<T: IdType> {
@@ -11,24 +30,244 @@ pub struct ThreadSafeIdManager<T: IdType> {
 }
 
 impl<T: IdType> ThreadSafeIdManager<T> {
-    pub fn new(reuse_policy: ReusePolicy) -> Self {
-        let manager = Arc::new(Mutex::new(IdManager::<T>::new(reuse_policy)));
+    pub fn new(strategy: impl IntoAllocationStrategy<T>) -> Self {
+        let manager = Arc::new(Mutex::new(IdManager::<T>::new(strategy)));
 
         ThreadSafeIdManager { manager }
     }
 
-    pub fn new_limited_range(reuse_policy: ReusePolicy, min_id: T, max_id: T) -> Self {
-        let manager = Arc::new(Mutex::new(IdManager::<T>::new_limited_range(reuse_policy, min_id, max_id)));
+    pub fn new_limited_range(strategy: impl IntoAllocationStrategy<T>, min_id: T, max_id: T) -> Self {
+        let manager = Arc::new(Mutex::new(IdManager::<T>::new_limited_range(strategy, min_id, max_id)));
 
         ThreadSafeIdManager { manager }
     }
 
+    pub fn new_with_ranges(strategy: impl IntoAllocationStrategy<T>, ranges: &[RangeInclusive<T>]) -> Self {
+        let manager = Arc::new(Mutex::new(IdManager::<T>::new_with_ranges(strategy, ranges)));
+
+        ThreadSafeIdManager { manager }
+    }
+
+    pub fn from_state(state: IdManagerState<T>) -> Result<Self, StateError> {
+        let manager = Arc::new(Mutex::new(IdManager::<T>::from_state(state)?));
+
+        Ok(ThreadSafeIdManager { manager })
+    }
+
+    pub fn to_state(&self) -> Result<IdManagerState<T>, StateError> {
+        let locked = self.lock();
+
+        locked.to_state()
+    }
+
+    pub fn write_snapshot(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        let locked = self.lock();
+
+        locked.write_snapshot(writer)
+    }
+
+    pub fn read_snapshot(reader: impl Read) -> Result<Self, SnapshotError> {
+        let manager = Arc::new(Mutex::new(IdManager::<T>::read_snapshot(reader)?));
+
+        Ok(ThreadSafeIdManager { manager })
+    }
+
+    pub fn recover(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        let manager = Arc::new(Mutex::new(IdManager::<T>::recover(path)?));
+
+        Ok(ThreadSafeIdManager { manager })
+    }
+
+    pub fn enable_journal(&self, path: impl AsRef<Path>) -> Result<(), JournalError> {
+        let mut locked = self.lock();
+
+        locked.enable_journal(path)
+    }
+
+    pub fn compact_journal(&self) -> Result<(), JournalError> {
+        let mut locked = self.lock();
+
+        locked.compact_journal()
+    }
+
+    pub fn set_clock(&self, clock: Box<dyn Clock>) {
+        let mut locked = self.lock();
+
+        locked.set_clock(clock);
+    }
+
+    pub fn set_quarantine(&self, period: Option<QuarantinePeriod>) {
+        let mut locked = self.lock();
+
+        locked.set_quarantine(period);
+    }
+
+    pub fn add_observer(&self, observer: Arc<dyn IdManagerObserver<T>>) {
+        let mut locked = self.lock();
+
+        locked.add_observer(observer);
+    }
+
+    pub fn set_low_watermark(&self, watermark: Option<u128>) {
+        let mut locked = self.lock();
+
+        locked.set_low_watermark(watermark);
+    }
+
+    pub fn enable_leak_detection(&self, capture_backtraces: bool) {
+        let mut locked = self.lock();
+
+        locked.enable_leak_detection(capture_backtraces);
+    }
+
+    pub fn outstanding_allocations(&self) -> Vec<TrackedAllocation<T>> {
+        let locked = self.lock();
+
+        locked.outstanding_allocations()
+    }
+
+    pub fn released_allocations(&self) -> Vec<TrackedAllocation<T>> {
+        let locked = self.lock();
+
+        locked.released_allocations()
+    }
+
+    pub fn leak_report(&self) -> String {
+        let locked = self.lock();
+
+        locked.leak_report()
+    }
+
+    pub fn enable_generations(&self) {
+        let mut locked = self.lock();
+
+        locked.enable_generations();
+    }
+
+    pub fn flush_quarantine(&self) {
+        let mut locked = self.lock();
+
+        locked.flush_quarantine();
+    }
+
+    pub fn extend_range(&self, new_min: T, new_max: T) {
+        let mut locked = self.lock();
+
+        locked.extend_range(new_min, new_max);
+    }
+
+    pub fn try_extend_range(&self, new_min: T, new_max: T) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_extend_range(new_min, new_max)
+    }
+
+    pub fn shrink_range(&self, new_min: T, new_max: T) -> Result<(), Vec<Interval<T>>> {
+        let mut locked = self.lock();
+
+        locked.shrink_range(new_min, new_max)
+    }
+
+    pub fn try_shrink_range(&self, new_min: T, new_max: T) -> Result<(), ShrinkRangeError<T>> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_shrink_range(new_min, new_max)
+    }
+
+    pub fn reserve(&self, lower: T, upper: T, label: &str) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.reserve(lower, upper, label)
+    }
+
+    pub fn reservations(&self) -> Vec<Reservation<T>> {
+        let locked = self.lock();
+
+        locked.reservations()
+    }
+
     pub fn dump(&self) -> String {
         let locked = self.lock();
 
         locked.dump()
     }
 
+    pub fn is_free(&self, id: T) -> bool {
+        let locked = self.lock();
+
+        locked.is_free(id)
+    }
+
+    pub fn is_allocated(&self, id: T) -> bool {
+        let locked = self.lock();
+
+        locked.is_allocated(id)
+    }
+
+    pub fn next_free_at_or_after(&self, id: T) -> Option<T> {
+        let locked = self.lock();
+
+        locked.next_free_at_or_after(id)
+    }
+
+    pub fn prev_free_at_or_before(&self, id: T) -> Option<T> {
+        let locked = self.lock();
+
+        locked.prev_free_at_or_before(id)
+    }
+
+    pub fn capacity(&self) -> Option<u128> {
+        let locked = self.lock();
+
+        locked.capacity()
+    }
+
+    pub fn free_count(&self) -> Option<u128> {
+        let locked = self.lock();
+
+        locked.free_count()
+    }
+
+    pub fn allocated_count(&self) -> Option<u128> {
+        let locked = self.lock();
+
+        locked.allocated_count()
+    }
+
+    pub fn interval_count(&self) -> usize {
+        let locked = self.lock();
+
+        locked.interval_count()
+    }
+
+    pub fn largest_free_block(&self) -> Option<Interval<T>> {
+        let locked = self.lock();
+
+        locked.largest_free_block()
+    }
+
+    // these iterate over a snapshot that is taken while the lock is held
+
+    pub fn free_intervals(&self) -> impl Iterator<Item = RangeInclusive<T>> {
+        let locked = self.lock();
+
+        locked.free_intervals().collect::<Vec<_>>().into_iter()
+    }
+
+    pub fn allocated_intervals(&self) -> impl Iterator<Item = RangeInclusive<T>> {
+        let locked = self.lock();
+
+        locked.allocated_intervals().collect::<Vec<_>>().into_iter()
+    }
+
+    pub fn free_values(&self) -> impl Iterator<Item = T> {
+        Values::new(self.free_intervals())
+    }
+
+    pub fn allocated_values(&self) -> impl Iterator<Item = T> {
+        Values::new(self.allocated_intervals())
+    }
+
     pub fn can_allocate(&self) -> bool {
         let locked = self.lock();
 
@@ -41,37 +280,209 @@ impl<T: IdType> ThreadSafeIdManager<T> {
         locked.allocate()
     }
 
+    #[track_caller]
     pub fn allocate_id(&self) -> SmartId<T> {
         SmartId::new(self.manager.clone())
     }
 
+    #[track_caller]
+    pub fn try_allocate_id(&self) -> Result<SmartId<T>, IdError> {
+        SmartId::try_new(self.manager.clone())
+    }
+
+    pub fn allocate_id_for(&self, owner: &str) -> T {
+        let mut locked = self.lock();
+
+        locked.allocate_for(owner)
+    }
+
+    pub fn try_allocate_id_for(&self, owner: &str) -> Result<T, IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_allocate_for(owner)
+    }
+
+    pub fn owner_of(&self, id: T) -> Option<String> {
+        let locked = self.lock();
+
+        locked.owner_of(id).map(str::to_string)
+    }
+
+    pub fn ids_owned_by(&self, owner: &str) -> Vec<T> {
+        let locked = self.lock();
+
+        locked.ids_owned_by(owner)
+    }
+
+    pub fn free_all_owned_by(&self, owner: &str) -> Vec<T> {
+        let mut locked = self.lock();
+
+        locked.free_all_owned_by(owner)
+    }
+
+    pub fn lease(&self, ttl: Duration) -> Lease<T> {
+        let mut locked = self.lock();
+
+        locked.lease(ttl)
+    }
+
+    pub fn try_lease(&self, ttl: Duration) -> Result<Lease<T>, IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_lease(ttl)
+    }
+
+    pub fn renew(&self, id: T, ttl: Duration) -> Result<Lease<T>, IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.renew(id, ttl)
+    }
+
+    pub fn lease_of(&self, id: T) -> Option<Lease<T>> {
+        let locked = self.lock();
+
+        locked.lease_of(id)
+    }
+
+    pub fn reap_expired(&self, now: Instant) -> Vec<T> {
+        let mut locked = self.lock();
+
+        locked.reap_expired(now)
+    }
+
+    #[track_caller]
+    pub fn allocate_generational_id(&self) -> SmartId<T> {
+        match self.try_allocate_generational_id() {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[track_caller]
+    pub fn try_allocate_generational_id(&self) -> Result<SmartId<T>, IdError> {
+        SmartId::try_new_generational(self.manager.clone())
+    }
+
+    pub fn is_current(&self, id: GenerationalId<T>) -> bool {
+        let locked = self.lock();
+
+        locked.is_current(id)
+    }
+
+    #[track_caller]
+    pub fn allocate_specific(&self, id: T) -> Result<SmartId<T>, IdError> {
+        SmartId::try_new_specific(self.manager.clone(), id)
+    }
+
+    #[track_caller]
+    pub fn allocate_near(&self, hint: T, direction: Direction) -> SmartId<T> {
+        match self.try_allocate_near(hint, direction) {
+            Ok(id) => id,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[track_caller]
+    pub fn try_allocate_near(&self, hint: T, direction: Direction) -> Result<SmartId<T>, IdError> {
+        SmartId::try_new_with(self.manager.clone(), |locked| locked.try_allocate_near(hint, direction))
+    }
+
+    #[track_caller]
+    pub fn allocate_block(&self, count: T) -> SmartIdBlock<T> {
+        SmartIdBlock::new(self.manager.clone(), count)
+    }
+
+    #[track_caller]
+    pub fn try_allocate_block(&self, count: T) -> Result<SmartIdBlock<T>, IdError> {
+        SmartIdBlock::try_new(self.manager.clone(), count)
+    }
+
+    #[track_caller]
+    pub fn allocate_aligned(&self, count: T, alignment: T) -> SmartIdBlock<T> {
+        SmartIdBlock::new_aligned(self.manager.clone(), count, alignment)
+    }
+
+    #[track_caller]
+    pub fn try_allocate_aligned(&self, count: T, alignment: T) -> Result<SmartIdBlock<T>, IdError> {
+        SmartIdBlock::try_new_aligned(self.manager.clone(), count, alignment)
+    }
+
     fn free(&self, id: T) {
         let mut locked = self.lock();
 
         locked.free(id)
     }
 
+    pub fn try_free(&self, id: T) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_free(id)
+    }
+
+    pub fn free_interval(&self, lower: T, upper: T) {
+        let mut locked = self.lock();
+
+        locked.free_interval(lower, upper)
+    }
+
+    pub fn try_free_interval(&self, lower: T, upper: T) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_free_interval(lower, upper)
+    }
+
+    pub fn try_free_generational(&self, id: GenerationalId<T>) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_free_generational(id)
+    }
+
     pub fn mark_value_as_used(&self, id: T) {
         let mut locked = self.lock();
 
         locked.mark_value_as_used(id);
     }
 
+    pub fn try_mark_value_as_used(&self, id: T) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_mark_value_as_used(id)
+    }
+
     pub fn mark_interval_as_used(&self, lower: T, upper: T) {
         let mut locked = self.lock();
 
         locked.mark_interval_as_used(lower, upper);
     }
 
-    fn lock(&self) -> MutexGuard<IdManager<T>> {
+    pub fn try_mark_interval_as_used(&self, lower: T, upper: T) -> Result<(), IdError> {
+        let mut locked = self.try_lock()?;
+
+        locked.try_mark_interval_as_used(lower, upper)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, IdManager<T>> {
         self.manager.lock().unwrap()
     }
+
+    fn try_lock(&self) -> Result<MutexGuard<'_, IdManager<T>>, IdError> {
+        self.manager.lock().map_err(|_| IdError::LockPoisoned)
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
+    use std::time::Duration;
+
+    use crate::allocation_strategy::AllocationStrategy;
+    use crate::clock::ManualClock;
+    use crate::intervals::Intervals;
     use crate::reuse_policy::ReusePolicy::ReuseFast;
+    use crate::reuse_policy::ReusePolicy::ReuseOldestFreed;
+    use crate::reuse_policy::ReusePolicy::ReuseRandom;
     use crate::reuse_policy::ReusePolicy::ReuseSlow;
+    use crate::reuse_policy::ReusePolicy::ReuseSlowDescending;
 
     use super::*;
 
@@ -121,6 +532,17 @@ mod tests {
         assert_eq!(manager.dump(), "[10,50]");
     }
 
+    #[test]
+    fn test_new_with_ranges() {
+        let manager = ThreadSafeIdManager::<u16>::new_with_ranges(ReuseSlow, &[100..=999, 5000..=5999]);
+
+        assert_eq!(manager.dump(), "[100,999], [5000,5999]");
+
+        let id = manager.allocate_near(2000, Direction::Nearest);
+
+        assert_eq!(id.value(), &999);
+    }
+
     #[test]
     fn test_can_allocate() {
         let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
@@ -217,37 +639,464 @@ mod tests {
     }
 
     #[test]
-    fn test_create_one_smart_id() {
-        let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
+    fn test_try_free() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
 
-        {
-            let _id = manager.allocate_id();
+        assert_eq!(manager.allocate(), 10);
 
-            assert_eq!(manager.dump(), "[1,255]");
-        }
+        assert_eq!(manager.try_free(10), Ok(()));
 
-        assert_eq!(manager.dump(), "[0,255]");
+        assert_eq!(manager.try_free(10), Err(IdError::NotAllocated));
+
+        assert_eq!(manager.try_free(51), Err(IdError::OutOfRange));
+
+        assert_eq!(manager.dump(), "[10,50]");
     }
 
     #[test]
-    fn test_create_multiple_smart_ids() {
-        let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
+    fn test_membership_queries() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
 
-        assert_eq!(manager.dump(), "[0,255]");
+        let id = manager.allocate_id();
 
-        {
-            let id1 = manager.allocate_id();
+        assert_eq!(manager.is_allocated(0), true);
+        assert_eq!(manager.is_free(0), false);
+        assert_eq!(manager.is_free(1), true);
 
-            let expected_id1: u8 = 0;
+        assert_eq!(manager.next_free_at_or_after(0), Some(1));
+        assert_eq!(manager.prev_free_at_or_before(0), None);
 
-            assert_eq!(id1.value(), &expected_id1);
+        drop(id);
 
-            assert_eq!(manager.dump(), "[1,255]");
+        assert_eq!(manager.is_allocated(0), false);
+        assert_eq!(manager.is_free(0), true);
 
-            {
-                let mut id2 = manager.allocate_id();
+        assert_eq!(manager.prev_free_at_or_before(0), Some(0));
+    }
 
-                let expected_id2: u8 = 1;
+    #[test]
+    fn test_statistics() {
+        let manager = ThreadSafeIdManager::<u8>::new(ReuseFast);
+
+        assert_eq!(manager.capacity(), Some(256));
+
+        let block = manager.allocate_block(10);
+
+        let id = manager.allocate_specific(100).unwrap();
+
+        assert_eq!(manager.free_count(), Some(245));
+        assert_eq!(manager.allocated_count(), Some(11));
+        assert_eq!(manager.interval_count(), 2);
+        assert_eq!(manager.largest_free_block(), Some(Interval::new(101, 255)));
+
+        drop(block);
+        drop(id);
+
+        assert_eq!(manager.allocated_count(), Some(0));
+        assert_eq!(manager.interval_count(), 1);
+    }
+
+    #[test]
+    fn test_iterators() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        let block = manager.allocate_block(3);
+
+        let id = manager.allocate_specific(7).unwrap();
+
+        assert_eq!(manager.free_intervals().collect::<Vec<_>>(), vec![3..=6, 8..=9]);
+
+        assert_eq!(manager.allocated_intervals().collect::<Vec<_>>(), vec![0..=2, 7..=7]);
+
+        assert_eq!(manager.free_values().collect::<Vec<_>>(), vec![3, 4, 5, 6, 8, 9]);
+
+        assert_eq!(manager.allocated_values().collect::<Vec<_>>(), vec![0, 1, 2, 7]);
+
+        drop(block);
+        drop(id);
+
+        assert_eq!(manager.allocated_intervals().next(), None);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn events(&self) -> Vec<String> {
+            self.events.lock().unwrap().drain(..).collect()
+        }
+    }
+
+    impl IdManagerObserver<u8> for Recorder {
+        fn on_allocate(&self, lower: u8, upper: u8) {
+            self.events.lock().unwrap().push(format!("allocate [{},{}]", lower, upper));
+        }
+
+        fn on_free(&self, lower: u8, upper: u8) {
+            self.events.lock().unwrap().push(format!("free [{},{}]", lower, upper));
+        }
+
+        fn on_mark_used(&self, lower: u8, upper: u8) {
+            self.events.lock().unwrap().push(format!("mark used [{},{}]", lower, upper));
+        }
+
+        fn on_exhausted(&self) {
+            self.events.lock().unwrap().push("exhausted".to_string());
+        }
+
+        fn on_low_watermark(&self, free_count: u128) {
+            self.events.lock().unwrap().push(format!("low watermark {}", free_count));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 3);
+
+        let recorder = Arc::new(Recorder::default());
+
+        manager.add_observer(recorder.clone());
+
+        manager.set_low_watermark(Some(1));
+
+        {
+            let _id = manager.allocate_id();
+
+            let _block = manager.allocate_block(2);
+
+            assert_eq!(recorder.events(), vec!["allocate [0,0]", "allocate [1,2]", "low watermark 1"]);
+
+            manager.mark_value_as_used(3);
+
+            assert!(matches!(manager.try_allocate_id(), Err(IdError::Exhausted)));
+
+            assert_eq!(recorder.events(), vec!["mark used [3,3]", "exhausted"]);
+        }
+
+        assert_eq!(recorder.events(), vec!["free [1,2]", "free [0,0]"]);
+
+        manager.free(3);
+
+        let _id = manager.allocate_id();
+
+        let _block = manager.allocate_block(2);
+
+        assert_eq!(recorder.events(), vec!["free [3,3]", "allocate [0,0]", "allocate [1,2]", "low watermark 1"]);
+
+        // 3 is still free, so missing it isn't exhaustion
+
+        assert!(matches!(manager.try_allocate_block(2), Err(IdError::NoSuitableBlock)));
+        assert!(matches!(manager.try_allocate_near(2, Direction::AtOrBelow), Err(IdError::NoFreeIdInDirection)));
+
+        assert!(recorder.events().is_empty());
+    }
+
+    #[test]
+    fn test_leak_detection() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 99);
+
+        let mut leaked = manager.allocate_id();
+
+        assert_eq!(manager.outstanding_allocations().len(), 0);
+
+        manager.enable_leak_detection(false);
+
+        let line = line!() + 1;
+        let id = manager.allocate_id();
+
+        let mut block = manager.allocate_block(5);
+
+        let outstanding = manager.outstanding_allocations();
+
+        assert_eq!(outstanding.len(), 2);
+
+        assert_eq!(outstanding[0].lower(), 1);
+        assert_eq!(outstanding[0].site().location().file(), file!());
+        assert_eq!(outstanding[0].site().location().line(), line);
+        assert_eq!(outstanding[0].site().backtrace(), None);
+
+        assert_eq!(outstanding[1].lower(), 2);
+        assert_eq!(outstanding[1].upper(), 6);
+
+        leaked.release();
+
+        assert_eq!(manager.released_allocations().len(), 0);
+
+        block.release();
+
+        assert_eq!(manager.outstanding_allocations().len(), 1);
+
+        let released = manager.released_allocations();
+
+        assert_eq!(released.len(), 1);
+
+        assert_eq!(manager.leak_report(), format!("outstanding: {}\nreleased: {}\n", outstanding[0], released[0]));
+
+        drop(id);
+
+        manager.free_interval(2, 6);
+
+        assert_eq!(manager.leak_report(), "");
+    }
+
+    #[test]
+    fn test_ownership() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        assert_eq!(manager.allocate_id_for("client1"), 0);
+        assert_eq!(manager.allocate_id_for("client2"), 1);
+        assert_eq!(manager.allocate_id_for("client1"), 2);
+
+        let _id = manager.allocate_id();
+
+        assert_eq!(manager.owner_of(0), Some("client1".to_string()));
+        assert_eq!(manager.owner_of(1), Some("client2".to_string()));
+        assert_eq!(manager.owner_of(3), None);
+
+        assert_eq!(manager.ids_owned_by("client1"), vec![0, 2]);
+        assert_eq!(manager.ids_owned_by("client3"), Vec::<u8>::new());
+
+        manager.try_free(2).unwrap();
+
+        assert_eq!(manager.owner_of(2), None);
+
+        assert_eq!(manager.allocate_id_for("client3"), 2);
+
+        assert_eq!(manager.free_all_owned_by("client1"), vec![0]);
+
+        assert_eq!(manager.dump(), "[0], [4,9]");
+
+        assert_eq!(manager.ids_owned_by("client1"), Vec::<u8>::new());
+        assert_eq!(manager.ids_owned_by("client2"), vec![1]);
+        assert_eq!(manager.ids_owned_by("client3"), vec![2]);
+    }
+
+    #[test]
+    fn test_leases() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        let clock = ManualClock::new();
+
+        manager.set_clock(Box::new(clock.clone()));
+
+        let lease1 = manager.lease(Duration::from_secs(10));
+        let lease2 = manager.lease(Duration::from_secs(20));
+
+        assert_eq!(lease1.id(), 0);
+        assert_eq!(lease2.id(), 1);
+
+        assert_eq!(lease2.expiry(), lease1.expiry() + Duration::from_secs(10));
+
+        assert_eq!(manager.lease_of(0), Some(lease1));
+        assert_eq!(manager.lease_of(2), None);
+
+        clock.advance(Duration::from_secs(5));
+
+        let renewed = manager.renew(0, Duration::from_secs(30)).unwrap();
+
+        assert_eq!(renewed.expiry(), lease1.expiry() + Duration::from_secs(25));
+
+        assert_eq!(manager.renew(2, Duration::from_secs(30)), Err(IdError::NotLeased));
+
+        clock.advance(Duration::from_secs(15));
+
+        assert_eq!(manager.renew(1, Duration::from_secs(30)), Err(IdError::LeaseExpired));
+
+        assert_eq!(manager.reap_expired(clock.now()), vec![1]);
+
+        assert_eq!(manager.dump(), "[1,9]");
+
+        assert_eq!(manager.renew(1, Duration::from_secs(30)), Err(IdError::NotLeased));
+
+        manager.try_free(0).unwrap();
+
+        assert_eq!(manager.lease_of(0), None);
+
+        assert_eq!(manager.reap_expired(clock.now() + Duration::from_secs(60)), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let manager = ThreadSafeIdManager::<u64>::new(ReuseSlow);
+
+        let mut id = manager.allocate_id();
+
+        id.release();
+
+        let _block = manager.allocate_block(10);
+
+        let mut buffer = Vec::new();
+
+        manager.write_snapshot(&mut buffer).unwrap();
+
+        let restored = ThreadSafeIdManager::<u64>::read_snapshot(&buffer[..]).unwrap();
+
+        assert_eq!(restored.dump(), "[11,18446744073709551615]");
+
+        assert_eq!(restored.allocate_id().value(), &11);
+
+        assert!(matches!(ThreadSafeIdManager::<u32>::read_snapshot(&buffer[..]), Err(SnapshotError::TypeWidthMismatch { expected: 4, found: 8 })));
+    }
+
+    #[test]
+    fn test_journal() {
+        let path = std::env::temp_dir().join(format!("id-manager-thread-safe-{}.journal", std::process::id()));
+
+        let (released, dump) = {
+            let manager = ThreadSafeIdManager::<u32>::new_limited_range(ReuseSlow, 0, 999);
+
+            let before = manager.allocate_id();
+
+            manager.enable_journal(&path).unwrap();
+
+            let mut id = manager.allocate_id();
+
+            let block = manager.allocate_block(10);
+
+            manager.mark_interval_as_used(500, 509);
+
+            manager.reserve(900, 999, "hardware").unwrap();
+
+            drop(manager.allocate_id());
+
+            drop(before);
+
+            drop(block);
+
+            (id.release(), manager.dump())
+        };
+
+        assert_eq!(dump, "[0], [2,499], [510,899]");
+
+        let manager = ThreadSafeIdManager::<u32>::recover(&path).unwrap();
+
+        assert_eq!(manager.dump(), dump);
+
+        assert_eq!(manager.is_allocated(released), true);
+
+        assert_eq!(manager.reservations().len(), 1);
+
+        assert_eq!(*manager.allocate_id().value(), 13);
+
+        manager.compact_journal().unwrap();
+
+        let compacted_length = std::fs::metadata(&path).unwrap().len();
+
+        manager.try_free(released).unwrap();
+
+        assert!(std::fs::metadata(&path).unwrap().len() > compacted_length);
+
+        let recovered = ThreadSafeIdManager::<u32>::recover(&path).unwrap();
+
+        assert_eq!(recovered.dump(), manager.dump());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compact_journal_when_not_enabled() {
+        let manager = ThreadSafeIdManager::<u32>::new(ReuseSlow);
+
+        assert!(matches!(manager.compact_journal(), Err(JournalError::NotEnabled)));
+    }
+
+    #[test]
+    fn test_free_interval() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 99);
+
+        manager.mark_interval_as_used(10, 49);
+
+        manager.free_interval(20, 29);
+
+        assert_eq!(manager.dump(), "[0,9], [20,29], [50,99]");
+
+        assert!(matches!(manager.try_free_interval(15, 25), Err(IdError::NotAllocated)));
+
+        assert_eq!(manager.dump(), "[0,9], [20,29], [50,99]");
+
+        assert!(matches!(manager.try_free_interval(30, 49), Ok(())));
+
+        assert!(matches!(manager.try_free_interval(10, 19), Ok(())));
+
+        assert_eq!(manager.dump(), "[0,99]");
+    }
+
+    #[test]
+    #[should_panic(expected = "id is not currently allocated")]
+    fn test_free_interval_not_allocated() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 99);
+
+        manager.free_interval(0, 10);
+    }
+
+    #[test]
+    fn test_try_allocate_id() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseSlow, 10, 11);
+
+        let id1 = manager.try_allocate_id().unwrap();
+
+        assert_eq!(id1.value(), &10);
+
+        let id2 = manager.try_allocate_id().unwrap();
+
+        assert_eq!(id2.value(), &11);
+
+        assert!(matches!(manager.try_allocate_id(), Err(IdError::Exhausted)));
+
+        assert_eq!(manager.dump(), "");
+    }
+
+    #[test]
+    fn test_try_allocate_id_when_lock_poisoned() {
+        let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
+
+        let poisoner = manager.clone();
+
+        let _ = std::thread::spawn(move || {
+            let _locked = poisoner.lock();
+
+            panic!("poison the lock");
+        }).join();
+
+        assert!(matches!(manager.try_allocate_id(), Err(IdError::LockPoisoned)));
+
+        assert_eq!(manager.try_free(0), Err(IdError::LockPoisoned));
+    }
+
+    #[test]
+    fn test_create_one_smart_id() {
+        let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
+
+        {
+            let _id = manager.allocate_id();
+
+            assert_eq!(manager.dump(), "[1,255]");
+        }
+
+        assert_eq!(manager.dump(), "[0,255]");
+    }
+
+    #[test]
+    fn test_create_multiple_smart_ids() {
+        let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
+
+        assert_eq!(manager.dump(), "[0,255]");
+
+        {
+            let id1 = manager.allocate_id();
+
+            let expected_id1: u8 = 0;
+
+            assert_eq!(id1.value(), &expected_id1);
+
+            assert_eq!(manager.dump(), "[1,255]");
+
+            {
+                let mut id2 = manager.allocate_id();
+
+                let expected_id2: u8 = 1;
 
                 assert_eq!(id2.value(), &expected_id2);
 
@@ -272,6 +1121,234 @@ mod tests {
         assert_eq!(manager.dump(), "[0], [2,255]");
     }
 
+    #[test]
+    fn test_allocate_specific() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+
+        {
+            let id1 = manager.allocate_specific(42).unwrap();
+
+            assert_eq!(id1.value(), &42);
+
+            assert_eq!(manager.dump(), "[10,41], [43,50]");
+
+            assert!(matches!(manager.allocate_specific(42), Err(IdError::AlreadyAllocated)));
+
+            assert!(matches!(manager.allocate_specific(51), Err(IdError::OutOfRange)));
+
+            let id2 = manager.allocate_id();
+
+            assert_eq!(id2.value(), &10);
+        }
+
+        assert_eq!(manager.dump(), "[10,50]");
+    }
+
+    #[test]
+    fn test_allocate_near() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+
+        {
+            let id1 = manager.allocate_near(30, Direction::Nearest);
+
+            assert_eq!(id1.value(), &30);
+
+            let id2 = manager.allocate_near(30, Direction::AtOrBelow);
+
+            assert_eq!(id2.value(), &29);
+
+            let id3 = manager.allocate_near(30, Direction::Nearest);
+
+            assert_eq!(id3.value(), &31);
+
+            assert_eq!(manager.dump(), "[10,28], [32,50]");
+
+            assert!(matches!(manager.try_allocate_near(51, Direction::AtOrAbove), Err(IdError::NoFreeIdInDirection)));
+        }
+
+        assert_eq!(manager.dump(), "[10,50]");
+    }
+
+    #[test]
+    fn test_allocate_block() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseSlow, 10, 50);
+
+        {
+            let block1 = manager.allocate_block(10);
+
+            assert_eq!(block1.lower(), 10);
+            assert_eq!(block1.upper(), 19);
+
+            {
+                let id = manager.allocate_id();
+
+                assert_eq!(id.value(), &20);
+
+                let block2 = manager.allocate_block(30);
+
+                assert_eq!(block2.lower(), 21);
+                assert_eq!(block2.upper(), 50);
+
+                assert_eq!(manager.dump(), "");
+
+                assert!(matches!(manager.try_allocate_block(1), Err(IdError::Exhausted)));
+            }
+
+            assert_eq!(manager.dump(), "[20,50]");
+        }
+
+        assert_eq!(manager.dump(), "[10,50]");
+    }
+
+    #[test]
+    fn test_allocate_block_reuse_fast() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 10, 50);
+
+        let block1 = manager.allocate_block(5);
+
+        assert_eq!(block1.lower(), 10);
+
+        {
+            let _block2 = manager.allocate_block(5);
+
+            assert_eq!(manager.dump(), "[20,50]");
+        }
+
+        let block3 = manager.allocate_block(3);
+
+        assert_eq!(block3.lower(), 15);
+
+        assert_eq!(manager.dump(), "[18,50]");
+    }
+
+    #[test]
+    fn test_allocate_aligned() {
+        let manager = ThreadSafeIdManager::<u16>::new_limited_range(ReuseSlow, 1, 255);
+
+        {
+            let block1 = manager.allocate_aligned(64, 64);
+
+            assert_eq!(block1.lower(), 64);
+            assert_eq!(block1.upper(), 127);
+
+            let block2 = manager.allocate_aligned(64, 64);
+
+            assert_eq!(block2.lower(), 128);
+
+            let block3 = manager.allocate_aligned(64, 64);
+
+            assert_eq!(block3.lower(), 192);
+
+            assert!(matches!(manager.try_allocate_aligned(64, 64), Err(IdError::NoSuitableBlock)));
+
+            assert!(matches!(manager.try_allocate_aligned(64, 0), Err(IdError::InvalidAlignment)));
+
+            assert_eq!(manager.dump(), "[1,63]");
+        }
+
+        assert_eq!(manager.dump(), "[1,255]");
+    }
+
+    #[test]
+    fn test_quarantine() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        let clock = ManualClock::new();
+
+        manager.set_clock(Box::new(clock.clone()));
+
+        manager.set_quarantine(Some(QuarantinePeriod::Duration(Duration::from_secs(60))));
+
+        {
+            let id = manager.allocate_id();
+
+            assert_eq!(id.value(), &0);
+        }
+
+        assert_eq!(manager.dump(), "[1,9] quarantined: [0]");
+
+        assert_eq!(manager.allocate_id().value(), &1);
+
+        clock.advance(Duration::from_secs(60));
+
+        assert_eq!(manager.allocate_id().value(), &0);
+
+        assert_eq!(manager.dump(), "[1,9] quarantined: [0]");
+
+        manager.flush_quarantine();
+
+        assert_eq!(manager.dump(), "[0,9]");
+    }
+
+    #[test]
+    fn test_reserve() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        assert!(matches!(manager.reserve(0, 0, "network"), Ok(())));
+
+        assert_eq!(manager.allocate_id().value(), &1);
+
+        assert!(matches!(manager.try_free(0), Err(IdError::Reserved)));
+
+        assert!(matches!(manager.allocate_specific(0), Err(IdError::Reserved)));
+
+        assert_eq!(manager.reservations(), vec![Reservation::new(0, 0, "network")]);
+
+        assert_eq!(manager.dump(), "[1,9]");
+    }
+
+    #[test]
+    fn test_resize_range() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 10, 19);
+
+        let id = manager.allocate_id();
+
+        assert_eq!(id.value(), &10);
+
+        manager.extend_range(0, 29);
+
+        assert_eq!(manager.dump(), "[0,9], [11,29]");
+
+        assert_eq!(manager.shrink_range(11, 29), Err(vec![Interval::new(10, 10)]));
+
+        drop(id);
+
+        assert_eq!(manager.shrink_range(11, 29), Ok(()));
+
+        assert_eq!(manager.dump(), "[11,29]");
+
+        assert!(matches!(manager.try_extend_range(12, 29), Err(IdError::OutOfRange)));
+
+        assert_eq!(manager.try_shrink_range(10, 29), Err(ShrinkRangeError::Failed(IdError::OutOfRange)));
+    }
+
+    #[test]
+    fn test_generational_ids() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseFast, 0, 9);
+
+        manager.enable_generations();
+
+        let stale = {
+            let id = manager.allocate_generational_id();
+
+            assert_eq!(id.generational_id(), Some(GenerationalId::new(0, 0)));
+
+            id.generational_id().unwrap()
+        };
+
+        let id = manager.allocate_generational_id();
+
+        assert_eq!(id.generational_id(), Some(GenerationalId::new(0, 1)));
+
+        assert_eq!(manager.is_current(stale), false);
+
+        assert_eq!(manager.is_current(id.generational_id().unwrap()), true);
+
+        assert!(matches!(manager.try_free_generational(stale), Err(IdError::StaleGeneration)));
+
+        assert_eq!(manager.dump(), "[1,9]");
+    }
+
     #[test]
     fn test_reuse_fast() {
         let manager = ThreadSafeIdManager::<u8>::new(ReuseFast);
@@ -331,6 +1408,81 @@ mod tests {
         assert_eq!(manager.dump(), "");
     }
 
+    #[test]
+    fn test_reuse_slow_descending() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseSlowDescending, 10, 50);
+
+        {
+            let id1 = manager.allocate_id();
+
+            assert_eq!(id1.value(), &50);
+
+            let id2 = manager.allocate_id();
+
+            assert_eq!(id2.value(), &49);
+        }
+
+        assert_eq!(manager.allocate_id().value(), &48);
+
+        assert_eq!(manager.dump(), "[10,50]");
+    }
+
+    #[test]
+    fn test_reuse_oldest_freed() {
+        let manager = ThreadSafeIdManager::<u8>::new_limited_range(ReuseOldestFreed, 0, 2);
+
+        let id0 = manager.allocate_id();
+        let id1 = manager.allocate_id();
+        let id2 = manager.allocate_id();
+
+        drop(id2);
+        drop(id0);
+        drop(id1);
+
+        assert_eq!(manager.allocate_id().value(), &2);
+        assert_eq!(manager.allocate_id().value(), &0);
+        assert_eq!(manager.allocate_id().value(), &1);
+    }
+
+    #[test]
+    fn test_reuse_random() {
+        let manager1 = ThreadSafeIdManager::<u32>::new(ReuseRandom { seed: Some(3) });
+        let manager2 = ThreadSafeIdManager::<u32>::new(ReuseRandom { seed: Some(3) });
+
+        let id1 = manager1.allocate_id();
+        let id2 = manager1.allocate_id();
+
+        assert_ne!(id1.value(), id2.value());
+
+        assert_eq!(manager2.allocate_id().value(), id1.value());
+        assert_eq!(manager2.allocate_id().value(), id2.value());
+    }
+
+    struct HighestFirst;
+
+    impl AllocationStrategy<u8> for HighestFirst {
+        fn allocate(&mut self, free_ids: &Intervals<u8>) -> Option<u8> {
+            free_ids.last_value_at_or_before(u8::MAX)
+        }
+    }
+
+    #[test]
+    fn test_custom_strategy() {
+        let manager = ThreadSafeIdManager::<u8>::new(Box::new(HighestFirst));
+
+        {
+            let id1 = manager.allocate_id();
+
+            assert_eq!(id1.value(), &255);
+
+            let id2 = manager.allocate_id();
+
+            assert_eq!(id2.value(), &254);
+        }
+
+        assert_eq!(manager.dump(), "[0,255]");
+    }
+
     #[test]
     fn test_mark_value_as_used() {
         let manager = ThreadSafeIdManager::<u8>::new(ReuseSlow);
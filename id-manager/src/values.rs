@@ -0,0 +1,53 @@
+use std::ops::RangeInclusive;
+
+use crate::id_type::IdType;
+
+// RangeInclusive<T> is only an iterator for the built in integer types, so we step through the values ourselves
+
+pub struct Values<T: IdType, I: Iterator<Item = RangeInclusive<T>>> {
+    ranges: I,
+    current: Option<(T, T)>,
+}
+
+impl<T: IdType, I: Iterator<Item = RangeInclusive<T>>> Values<T, I> {
+    pub fn new(ranges: I) -> Self {
+        Values { ranges, current: None }
+    }
+}
+
+impl<T: IdType, I: Iterator<Item = RangeInclusive<T>>> Iterator for Values<T, I> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.current.is_none() {
+            self.current = self.ranges.next().map(|range| (*range.start(), *range.end()));
+        }
+
+        let (value, upper) = self.current?;
+
+        self.current = if value == upper { None } else { Some((value + T::one(), upper)) };
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values() {
+        let ranges = vec![0u8..=2, 5..=5, 254..=255];
+
+        let values: Vec<u8> = Values::new(ranges.into_iter()).collect();
+
+        assert_eq!(values, vec![0, 1, 2, 5, 254, 255]);
+    }
+
+    #[test]
+    fn test_values_when_empty() {
+        let ranges: Vec<RangeInclusive<u8>> = vec![];
+
+        assert_eq!(Values::new(ranges.into_iter()).next(), None);
+    }
+}